@@ -0,0 +1,200 @@
+//! 在 Wine、Lutris、Bottles、Steam (Proton) 与 Heroic 的前缀中查找游戏安装
+
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use walkdir::{DirEntry, WalkDir};
+
+use crate::game_config::read_game_version;
+use crate::language::DATA_DIRS;
+use crate::t;
+
+/// 前缀（或游戏库）根目录下的最大搜索深度
+///
+/// `compatdata/<id>/pfx/drive_c/Program Files/Genshin Impact/Genshin Impact game/GenshinImpact.exe` 为 7 层
+const MAX_DEPTH: usize = 9;
+
+/// 搜索时跳过的目录：系统目录、指向宿主文件系统的盘符链接与游戏数据目录
+const SKIPPED_DIRS: &[&str] = &["windows", "dosdevices", "shadercache"];
+
+/// 安装所在的启动器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Launcher {
+    Wine,
+    Lutris,
+    Bottles,
+    Steam,
+    Heroic,
+}
+
+impl fmt::Display for Launcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Launcher::Wine => "Wine",
+            Launcher::Lutris => "Lutris",
+            Launcher::Bottles => "Bottles",
+            Launcher::Steam => "Steam",
+            Launcher::Heroic => "Heroic",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 区服，按可执行文件名区分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// 国际服（`GenshinImpact.exe`）
+    Global,
+    /// 国服（`YuanShen.exe`）
+    China,
+}
+
+const EXECUTABLES: &[(&str, Region)] = &[("GenshinImpact.exe", Region::Global), ("YuanShen.exe", Region::China)];
+
+/// 找到的游戏安装
+#[derive(Debug, Clone, PartialEq)]
+pub struct Install {
+    pub game_dir: PathBuf,
+    pub launcher: Launcher,
+    pub region: Region,
+    /// `config.ini` 中的版本，读取失败时为空
+    pub version: Option<String>,
+}
+
+/// 需要搜索的根目录；Heroic 的默认目录在 `~/Games` 下，排在 Lutris 前面
+fn search_roots(home: &Path) -> Vec<(Launcher, PathBuf)> {
+    let mut roots = vec![(Launcher::Wine, home.join(".wine"))];
+
+    roots.push((Launcher::Heroic, home.join("Games/Heroic")));
+    roots.push((Launcher::Heroic, home.join(".var/app/com.heroicgameslauncher.hgl/Games/Heroic")));
+    roots.push((Launcher::Lutris, home.join("Games")));
+    roots.push((Launcher::Lutris, home.join(".var/app/net.lutris.Lutris/data/games")));
+
+    for bottles in [".local/share/bottles/bottles", ".var/app/com.usebottles.bottles/data/bottles/bottles"] {
+        roots.push((Launcher::Bottles, home.join(bottles)));
+    }
+
+    for steam in [
+        ".steam/steam",
+        ".local/share/Steam",
+        ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+    ] {
+        roots.push((Launcher::Steam, home.join(steam).join("steamapps/compatdata")));
+    }
+
+    roots
+}
+
+fn is_skipped(entry: &DirEntry) -> bool {
+    if !entry.file_type().is_dir() || entry.depth() == 0 {
+        return false;
+    }
+    let name = entry.file_name().to_string_lossy();
+    SKIPPED_DIRS.iter().chain(DATA_DIRS).any(|dir| name.eq_ignore_ascii_case(dir))
+}
+
+fn region_of(file_name: &str) -> Option<Region> {
+    EXECUTABLES
+        .iter()
+        .find(|(exe, _)| exe.eq_ignore_ascii_case(file_name))
+        .map(|(_, region)| *region)
+}
+
+/// 在 `home` 下的各个前缀中查找游戏安装，同一目录只列出一次
+pub fn discover_in(home: &Path) -> Vec<Install> {
+    let mut seen = HashSet::new();
+    let mut installs = Vec::new();
+
+    for (launcher, root) in search_roots(home) {
+        if !root.is_dir() {
+            continue;
+        }
+        debug!(root = %root.display(), %launcher, "{}", t!(SearchingInstalls));
+
+        let entries = WalkDir::new(&root)
+            .max_depth(MAX_DEPTH)
+            .into_iter()
+            .filter_entry(|entry| !is_skipped(entry))
+            .filter_map(|e| e.ok());
+        for entry in entries {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Some(region) = region_of(&entry.file_name().to_string_lossy()) else {
+                continue;
+            };
+            let Some(game_dir) = entry.path().parent() else {
+                continue;
+            };
+            let key = game_dir.canonicalize().unwrap_or_else(|_| game_dir.to_path_buf());
+            if !seen.insert(key) {
+                continue;
+            }
+
+            installs.push(Install {
+                game_dir: game_dir.to_path_buf(),
+                launcher,
+                region,
+                version: read_game_version(game_dir).ok().flatten(),
+            });
+        }
+    }
+
+    installs
+}
+
+/// 在当前用户的前缀中查找游戏安装，没有 `HOME` 时返回空列表
+pub fn discover() -> Vec<Install> {
+    match env::var_os("HOME") {
+        Some(home) if !home.is_empty() => discover_in(Path::new(&home)),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_discover_in_prefixes() {
+        let temp_dir = TempDir::new().unwrap();
+        let home = temp_dir.path();
+        let install = |path: &str, exe: &str, version: Option<&str>| {
+            let game_dir = home.join(path);
+            fs::create_dir_all(game_dir.join("GenshinImpact_Data")).unwrap();
+            fs::write(game_dir.join(exe), b"").unwrap();
+            if let Some(version) = version {
+                fs::write(game_dir.join("config.ini"), format!("[General]\ngame_version={}\n", version)).unwrap();
+            }
+            game_dir
+        };
+
+        let wine = install(".wine/drive_c/Program Files/Genshin Impact/Genshin Impact game", "GenshinImpact.exe", Some("5.0.0"));
+        let steam = install(
+            ".local/share/Steam/steamapps/compatdata/123/pfx/drive_c/Program Files/Genshin Impact/Genshin Impact game",
+            "YuanShen.exe",
+            None,
+        );
+        let heroic = install("Games/Heroic/Genshin Impact", "GenshinImpact.exe", Some("5.1.0"));
+        // 系统目录与游戏数据目录下的同名文件不算安装
+        install(".wine/drive_c/windows/system32", "GenshinImpact.exe", None);
+        install("Games/genshin/GenshinImpact_Data/backup", "GenshinImpact.exe", None);
+
+        let installs = discover_in(home);
+        assert_eq!(installs.len(), 3);
+
+        assert_eq!(installs[0].game_dir, wine);
+        assert_eq!(installs[0].launcher, Launcher::Wine);
+        assert_eq!(installs[0].version.as_deref(), Some("5.0.0"));
+        assert_eq!(installs[1].game_dir, heroic);
+        assert_eq!(installs[1].launcher, Launcher::Heroic);
+        assert_eq!(installs[2].game_dir, steam);
+        assert_eq!(installs[2].launcher, Launcher::Steam);
+        assert_eq!(installs[2].region, Region::China);
+        assert_eq!(installs[2].version, None);
+    }
+}
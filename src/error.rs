@@ -0,0 +1,159 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::t;
+
+/// 更新器的错误类型
+///
+/// 每个变体对应一个稳定的进程退出码，见 [`Error::exit_code`]
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{}", t!(ErrApi, .url, .message))]
+    Api { url: String, message: String },
+
+    #[error("{}", t!(ErrApiRetcode, .retcode, .message))]
+    ApiRetcode { retcode: i32, message: String },
+
+    #[error("{}", t!(ErrSchema, .field, .message))]
+    Schema { field: String, message: String },
+
+    #[error("{}", t!(ErrManifest, .name, .message))]
+    Manifest { name: String, message: String },
+
+    #[error("{}", t!(ErrDownload, .url, .message))]
+    Download { url: String, message: String },
+
+    #[error("{}", t!(ErrChecksum, .path.display(), .expected, .actual))]
+    Checksum { path: PathBuf, expected: String, actual: String },
+
+    #[error("{}", t!(ErrExtract, .path.display(), .source))]
+    Extract {
+        path: PathBuf,
+        #[source]
+        source: zip::result::ZipError,
+    },
+
+    #[error("{}", t!(ErrPatch, .file, .code.map_or_else(|| t!(ErrNoExitCode).to_string(), |c| c.to_string()), .stderr))]
+    Patch { file: String, code: Option<i32>, stderr: String },
+
+    #[error("{}", t!(ErrDelete, .path.display(), .source))]
+    Delete {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{}", t!(ErrCopy, .path.display(), .source))]
+    Copy {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{}", t!(ErrConfig, .path.display(), .message))]
+    Config { path: PathBuf, message: String },
+
+    #[error("{}", t!(ErrIo, .path.display(), .source))]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{}", t!(ErrPlan, .0))]
+    Plan(String),
+
+    #[error("{}", t!(ErrCancelled, .0))]
+    Cancelled(String),
+
+    #[error("{}", t!(ErrVerify, .missing, .mismatched))]
+    Verify { missing: usize, mismatched: usize },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// 进程退出码，供脚本区分失败原因
+    ///
+    /// | 退出码 | 含义 |
+    /// |---|---|
+    /// | 10 | API 请求失败、返回错误或格式不符，Sophon 清单无效 |
+    /// | 11 | 下载失败 |
+    /// | 12 | 校验失败 |
+    /// | 13 | 解压失败 |
+    /// | 14 | 补丁失败 |
+    /// | 15 | 删除失败 |
+    /// | 16 | 复制失败 |
+    /// | 17 | 配置错误 |
+    /// | 18 | 其他文件操作失败 |
+    /// | 19 | 权限不足 |
+    /// | 20 | 无法制定更新计划 |
+    /// | 21 | 用户取消 |
+    /// | 22 | 更新后校验失败 |
+    pub fn exit_code(&self) -> u8 {
+        if self.is_permission_denied() {
+            return 19;
+        }
+        match self {
+            Error::Api { .. } | Error::ApiRetcode { .. } | Error::Schema { .. } | Error::Manifest { .. } => 10,
+            Error::Download { .. } => 11,
+            Error::Checksum { .. } => 12,
+            Error::Extract { .. } => 13,
+            Error::Patch { .. } => 14,
+            Error::Delete { .. } => 15,
+            Error::Copy { .. } => 16,
+            Error::Config { .. } => 17,
+            Error::Io { .. } => 18,
+            Error::Plan(_) => 20,
+            Error::Cancelled(_) => 21,
+            Error::Verify { .. } => 22,
+        }
+    }
+
+    /// 是否由权限不足引起
+    pub fn is_permission_denied(&self) -> bool {
+        match self {
+            Error::Delete { source, .. } | Error::Copy { source, .. } | Error::Io { source, .. } => {
+                source.kind() == io::ErrorKind::PermissionDenied
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 为 I/O 错误附加路径
+pub trait IoContext<T> {
+    fn with_path(self, path: impl AsRef<Path>) -> Result<T>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn with_path(self, path: impl AsRef<Path>) -> Result<T> {
+        self.map_err(|source| Error::Io { path: path.as_ref().to_path_buf(), source })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::{format, Locale, Msg};
+
+    #[test]
+    fn test_exit_codes() {
+        let denied = Err::<(), _>(io::Error::from(io::ErrorKind::PermissionDenied))
+            .with_path("config.ini")
+            .unwrap_err();
+        assert_eq!(denied.exit_code(), 19);
+
+        let copy = Error::Copy {
+            path: PathBuf::from("a"),
+            source: io::Error::from(io::ErrorKind::NotFound),
+        };
+        assert_eq!(copy.exit_code(), 16);
+
+        let patch = Error::Patch { file: "a".to_string(), code: Some(1), stderr: "bad".to_string() };
+        assert_eq!(patch.exit_code(), 14);
+        // 不修改全局语言，避免影响并行运行的其他测试
+        assert_eq!(patch.to_string(), format(Msg::ErrPatch.text(), &[&"a", &1, &"bad"]));
+        assert_eq!(format(Msg::ErrPatch.text_in(Locale::ZhCn), &[&"a", &1, &"bad"]), "补丁失败: a（退出码 1）: bad");
+    }
+}
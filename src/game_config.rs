@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::Path;
+use crate::error::{Error, Result};
+
+pub(crate) const CONFIG_FILE: &str = "config.ini";
+const GENERAL_SECTION: &str = "General";
+
+fn config_err(path: &Path) -> impl FnOnce(std::io::Error) -> Error + '_ {
+    move |e| Error::Config { path: path.to_path_buf(), message: e.to_string() }
+}
+
+/// 读取游戏目录 `config.ini` 中 `[General]` 段的某个键
+fn read_general_key(game_dir: &Path, key: &str) -> Result<Option<String>> {
+    let path = game_dir.join(CONFIG_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let data = fs::read_to_string(&path).map_err(config_err(&path))?;
+    let mut in_general = false;
+    for line in data.lines() {
+        let line = line.trim();
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_general = section.trim() == GENERAL_SECTION;
+            continue;
+        }
+        if !in_general {
+            continue;
+        }
+        if let Some((k, v)) = line.split_once('=') {
+            if k.trim() == key {
+                let value = v.trim();
+                return Ok((!value.is_empty()).then(|| value.to_string()));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// 读取已安装的游戏版本
+pub fn read_game_version(game_dir: &Path) -> Result<Option<String>> {
+    read_general_key(game_dir, "game_version")
+}
+
+/// 在 ini 文本中设置 `[General]` 段的键值，保留其余内容与换行风格
+fn set_general_keys(data: &str, values: &[(&str, &str)]) -> String {
+    let newline = if data.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut lines: Vec<String> = data.lines().map(str::to_string).collect();
+    let mut pending: Vec<(&str, &str)> = values.to_vec();
+
+    let general_start = lines.iter().position(|line| line.trim() == format!("[{}]", GENERAL_SECTION));
+    let general_start = match general_start {
+        Some(idx) => idx,
+        None => {
+            lines.insert(0, format!("[{}]", GENERAL_SECTION));
+            0
+        }
+    };
+
+    let mut general_end = lines.len();
+    for (idx, line) in lines.iter_mut().enumerate().skip(general_start + 1) {
+        if line.trim().starts_with('[') {
+            general_end = idx;
+            break;
+        }
+        if let Some((k, _)) = line.split_once('=') {
+            let key = k.trim().to_string();
+            if let Some(pos) = pending.iter().position(|(pk, _)| *pk == key) {
+                let (_, value) = pending.remove(pos);
+                *line = format!("{}={}", key, value);
+            }
+        }
+    }
+
+    for (offset, (key, value)) in pending.into_iter().enumerate() {
+        lines.insert(general_end + offset, format!("{}={}", key, value));
+    }
+
+    let mut out = lines.join(newline);
+    out.push_str(newline);
+    out
+}
+
+/// 更新游戏目录 `config.ini` 中记录的版本号，文件不存在时创建
+/// 先写入临时文件再重命名，避免中途失败留下损坏的配置
+pub fn write_game_version(game_dir: &Path, version: &str, game_biz: &str) -> Result<()> {
+    let path = game_dir.join(CONFIG_FILE);
+    let data = if path.is_file() {
+        fs::read_to_string(&path).map_err(config_err(&path))?
+    } else {
+        String::new()
+    };
+
+    let mut values = vec![("game_version", version)];
+    if !game_biz.is_empty() {
+        values.push(("game_biz", game_biz));
+    }
+    let updated = set_general_keys(&data, &values);
+
+    let tmp_path = game_dir.join(format!("{}.tmp", CONFIG_FILE));
+    fs::write(&tmp_path, updated).map_err(config_err(&tmp_path))?;
+    fs::rename(&tmp_path, &path).map_err(config_err(&path))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_game_version_preserves_other_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = "[General]\r\nchannel=1\r\ncps=mihoyo\r\ngame_version=5.0.0\r\nsub_channel=0\r\n[launcher]\r\nkey=value\r\n";
+        fs::write(temp_dir.path().join(CONFIG_FILE), config).unwrap();
+
+        write_game_version(temp_dir.path(), "5.1.0", "hk4e_global").unwrap();
+
+        let data = fs::read_to_string(temp_dir.path().join(CONFIG_FILE)).unwrap();
+        assert_eq!(
+            data,
+            "[General]\r\nchannel=1\r\ncps=mihoyo\r\ngame_version=5.1.0\r\nsub_channel=0\r\ngame_biz=hk4e_global\r\n[launcher]\r\nkey=value\r\n"
+        );
+        assert_eq!(read_game_version(temp_dir.path()).unwrap().as_deref(), Some("5.1.0"));
+    }
+
+    #[test]
+    fn test_write_game_version_creates_file() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(read_game_version(temp_dir.path()).unwrap(), None);
+
+        write_game_version(temp_dir.path(), "5.1.0", "").unwrap();
+
+        let data = fs::read_to_string(temp_dir.path().join(CONFIG_FILE)).unwrap();
+        assert_eq!(data, "[General]\ngame_version=5.1.0\n");
+    }
+}
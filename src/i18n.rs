@@ -0,0 +1,366 @@
+//! 面向用户的文本目录（English / 简体中文）
+//!
+//! 语言按配置 `[ui] locale`、`LC_ALL`、`LC_MESSAGES`、`LANG` 的顺序确定，都未设置时使用简体中文
+
+use std::env;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+use serde::Deserialize;
+
+/// 界面语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Locale {
+    En,
+    ZhCn,
+}
+
+impl Locale {
+    /// 解析 `en`、`zh-CN`、`zh_CN.UTF-8` 等写法
+    pub fn parse(value: &str) -> Option<Locale> {
+        let value = value.trim().to_ascii_lowercase();
+        if value.starts_with("zh") {
+            Some(Locale::ZhCn)
+        } else if value.starts_with("en") || value == "c" || value == "posix" || value.starts_with("c.") {
+            Some(Locale::En)
+        } else {
+            None
+        }
+    }
+
+    /// 从环境变量检测，无法识别的语言使用英文
+    pub fn from_env() -> Locale {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|key| env::var(key).ok())
+            .find(|value| !value.is_empty())
+            .map_or(Locale::ZhCn, |value| Locale::parse(&value).unwrap_or(Locale::En))
+    }
+}
+
+impl TryFrom<String> for Locale {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Locale::parse(&value).ok_or_else(|| format!("unsupported locale `{}`, expected `en` or `zh-CN`", value))
+    }
+}
+
+const UNSET: u8 = u8::MAX;
+static LOCALE: AtomicU8 = AtomicU8::new(UNSET);
+
+/// 设置界面语言
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+/// 当前界面语言，未设置时从环境变量检测
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        UNSET => {
+            let locale = Locale::from_env();
+            set_locale(locale);
+            locale
+        }
+        value if value == Locale::En as u8 => Locale::En,
+        _ => Locale::ZhCn,
+    }
+}
+
+/// 依次用 `args` 替换模板中的 `{}`
+pub fn format(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(pos) = rest.find("{}") {
+        out.push_str(&rest[..pos]);
+        match args.next() {
+            Some(arg) => out.push_str(&arg.to_string()),
+            None => out.push_str("{}"),
+        }
+        rest = &rest[pos + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 取当前语言的文本，带参数时替换其中的 `{}`
+#[macro_export]
+macro_rules! t {
+    ($msg:ident) => {
+        $crate::i18n::Msg::$msg.text()
+    };
+    ($msg:ident, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::format($crate::i18n::Msg::$msg.text(), &[$(&$arg as &dyn ::std::fmt::Display),+])
+    };
+}
+
+macro_rules! messages {
+    ($($key:ident => $en:literal, $zh:literal;)*) => {
+        /// 文本条目
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Msg {
+            $($key,)*
+        }
+
+        impl Msg {
+            pub fn text_in(self, locale: Locale) -> &'static str {
+                match (self, locale) {
+                    $(
+                        (Msg::$key, Locale::En) => $en,
+                        (Msg::$key, Locale::ZhCn) => $zh,
+                    )*
+                }
+            }
+
+            pub fn text(self) -> &'static str {
+                self.text_in(locale())
+            }
+        }
+    };
+}
+
+messages! {
+    // 命令行
+    AppAbout => "Genshin Impact updater", "原神更新器";
+    ArgVerbose => "Show more details (repeatable, e.g. -vv)", "输出更详细的信息（可重复，如 -vv）";
+    ArgQuiet => "Only show warnings and errors", "只输出警告和错误";
+    ArgJson => "Print JSON progress events to stdout, one per line, instead of progress bars", "在标准输出逐行输出 JSON 进度事件，代替进度条";
+    ArgOffline => "Offline mode: use the cached manifest and already downloaded packages", "离线模式：使用缓存的清单与已下载的更新包";
+    ArgRepair => "Re-download files that fail verification after the update", "更新后校验失败的文件从资源地址重新下载";
+    ArgOnCopyError => "What to do when a file cannot be placed (defaults to the config, abort)", "文件放置失败时的处理方式，默认读取配置（abort）";
+    PolicyAbort => "Abort the update immediately", "立即中止更新";
+    PolicySkip => "Skip the file, list it at the end and leave it to verify and repair", "跳过该文件，结束时列出并交给校验修复";
+    PolicyRetry => "Retry a few times, then abort", "重试若干次，仍失败则中止";
+    ArgCopyRetries => "Retries per file under the retry policy", "retry 策略下每个文件的重试次数";
+    ArgOwner => "Owner for written files (unix only), defaults to the game dir owner when running as root", "写入文件的属主（仅 unix），以 root 运行时默认为游戏目录的属主";
+    UnknownUser => "unknown user `{}`", "未知用户 `{}`";
+    UnknownGroup => "unknown group `{}`", "未知用户组 `{}`";
+    ArgSequential => "Download all packages before extracting instead of in parallel", "先下载全部更新包再解压，不与下载并行";
+    ArgConfig => "Config file path, defaults to updater.toml in the current directory", "配置文件路径，默认读取当前目录下的 updater.toml";
+    ArgGameDir => "Game directory, prompted for when omitted", "游戏目录，未指定时交互输入";
+    ArgGame => "Game to update (biz or id) when the manifest has several", "清单中有多个游戏时要更新的游戏（biz 或 id）";
+    CmdApply => "Apply local update packages (game and voice packs) without network access", "应用本地的更新包（游戏与语音包），不访问网络";
+    ArgPackage => "Update package path, repeatable", "更新包路径，可重复指定";
+    ArgToVersion => "Version after the update, written to config.ini when the manifest has no match", "更新后的版本号，清单中找不到更新包时用于写入 config.ini";
+    CmdSophon => "Update to the latest version with Sophon chunks, downloading only what changed", "通过 Sophon 分块下载更新到最新版本，只下载变化的部分";
+    ArgLanguage => "Voice pack language to update, repeatable, defaults to the installed languages", "要更新的语音包语言，可重复指定，默认为已安装的语言";
+    CmdCleanOrphans => "List and delete files not in any pkg_version", "列出并删除不在 pkg_version 中的残留文件";
+    ArgYes => "Don't ask for confirmation", "不询问直接确认";
+    CmdCache => "Manage the downloaded package cache", "管理已下载的更新包缓存";
+    CmdCacheList => "List cached packages", "列出缓存的更新包";
+    CmdCachePrune => "Evict packages by the configured size and age limits", "按配置的大小与时间上限淘汰更新包";
+    ArgPruneAll => "Delete every cached package", "删除全部缓存的更新包";
+    CmdDiscover => "Find game installs in Wine, Lutris, Bottles, Steam and Heroic prefixes", "在 Wine、Lutris、Bottles、Steam 与 Heroic 前缀中查找游戏安装";
+
+    // 主流程
+    LogInitFailed => "❌ Failed to initialize logging: {}", "❌ 无法初始化日志: {}";
+    Starting => "🚀 Starting Genshin Impact updater...", "🚀 启动原神更新器...";
+    SkippedFiles => "⚠️ {} files could not be placed and were skipped:", "⚠️ {} 个文件放置失败被跳过:";
+    Verified => "🔍 Verified {} files", "🔍 已校验 {} 个文件";
+    VerifyFound => "⚠️ Verification found {} missing and {} corrupted files", "⚠️ 校验发现 {} 个缺失、{} 个损坏的文件";
+    NoResListUrl => "⚠️ No resource URL available, cannot repair", "⚠️ 没有可用的资源地址，无法修复";
+    RepairHint => "Use --repair to re-download corrupted files", "使用 --repair 重新下载损坏的文件";
+    VerifyFailedFile => "⚠️ Verification failed", "⚠️ 校验失败";
+    NoCachedManifest => "⚠️ No cached manifest, md5 cannot be checked", "⚠️ 没有缓存的清单，无法校验 md5";
+    UpdateDone => "✅ Update complete!", "✅ 完成更新！";
+    UpdateDoneVersion => "✅ Update complete! Current version {}", "✅ 完成更新！当前版本 {}";
+    UnknownInstalledLanguages => "⚠️ Unrecognized installed voice packs: {}", "⚠️ 无法识别的已安装语音包: {}";
+    LatestGameId => "Latest game id: {}", "最新游戏 id: {}";
+    LatestGameVersion => "Latest game version: {}", "最新游戏版本: {}";
+    InstalledVersion => "Installed game version: {}", "已安装游戏版本: {}";
+    RouteDirect => "A single patch upgrades {} to {}", "一个补丁即可从 {} 升级到 {}";
+    RouteMultiHop => "No patch from {} in the manifest, chaining cached patches: {}", "清单中没有从 {} 升级的补丁，串联缓存中的补丁: {}";
+    RouteFull => "No patch path from {} to {}, downloading the full {} package", "没有从 {} 到 {} 的补丁路线，下载完整的 {} 安装包";
+    RouteFullUnknown => "Installed version unknown, downloading the full {} package", "无法确定已安装版本，下载完整的 {} 安装包";
+    AlreadyLatest => "✅ Already up to date", "✅ 已是最新版本";
+    ChosenVersion => "Chosen version: {}", "选择的版本: {}";
+    InstalledLanguage => "Installed languages: {}", "已安装语言: {}";
+    ChosenLanguage => "Chosen languages: {}", "选择的语言: {}";
+    UpdateNotStarted => "update not started", "未开始更新";
+    CacheTotal => "{} packages, {}", "共 {} 个更新包，{}";
+    CachePruned => "Removed {} packages, freed {}", "已删除 {} 个更新包，释放 {}";
+    NoOrphans => "✅ No orphaned files", "✅ 没有残留文件";
+    OrphanTotal => "{} orphaned files, {}", "共 {} 个残留文件，{}";
+    ConfirmDeleteOrphans => "Delete these files?", "删除这些文件？";
+    OrphansKept => "orphaned files were not deleted", "未删除残留文件";
+    Freed => "🗑️ Freed {}", "🗑️ 已释放 {}";
+    DiscoverNone => "No game installs found", "没有找到游戏安装";
+    DiscoverTotal => "Found {} game installs", "找到 {} 个游戏安装";
+
+    // 交互
+    TermFailed => "terminal interaction failed: {}", "终端交互失败: {}";
+    SelectionCancelled => "selection cancelled", "已取消选择";
+    PromptGameDir => "Enter game dir", "输入游戏目录";
+    PromptInstall => "Choose game dir", "选择游戏目录";
+    EnterManually => "Enter another directory...", "输入其他目录...";
+    RegionGlobal => "Global", "国际服";
+    RegionChina => "China", "国服";
+    VersionUnknown => "unknown version", "未知版本";
+    NotADirectory => "not a directory", "不是目录";
+    NoGame => "No game in the manifest", "清单中没有游戏";
+    PromptGame => "Choose game", "选择游戏";
+    PromptFromVersion => "Choose which version to upgrade from", "选择要从哪个版本升级";
+    GamePkgSize => "game package {}", "游戏包 {}";
+    PromptLanguages => "Choose the voice packs to update (Space to toggle)", "选择要更新的语音包（空格勾选）";
+    ConfirmMissingLanguages => "Installed languages {} will not be updated, continue?", "以下已安装语言不会被更新: {}，继续？";
+    MissingLanguages => "⚠️ Installed languages {} will not be updated", "⚠️ 以下已安装语言不会被更新: {}";
+    UnknownLanguages => "No voice pack for languages: {}", "没有以下语言的语音包: {}";
+    UnknownGame => "No game `{}` in the manifest", "清单中没有游戏 `{}`";
+    GameRequired => "The manifest has several games, choose one with --game", "清单中有多个游戏，请用 --game 指定";
+    GameDirRequired => "Found {} game installs, specify one with --game-dir", "找到 {} 个游戏安装，请用 --game-dir 指定";
+    GameDirRequiredToDelete => "This command deletes files, specify the game dir with --game-dir when running unattended", "该命令会删除文件，无人值守时请用 --game-dir 指定游戏目录";
+    UsingInstall => "Using game dir {}", "使用游戏目录 {}";
+    ConfirmNeedsYes => "cannot ask for confirmation without a terminal, pass --yes", "没有终端无法确认，请指定 --yes";
+    PlanPackage => "package", "更新包";
+    PlanSize => "size", "大小";
+    PlanCached => "cached", "已缓存";
+    PlanDecompressed => "decompressed", "解压后";
+    PlanTotal => "total", "合计";
+    PlanEstimate => "To download {}, disk change {}, estimated time {}", "需下载 {}，磁盘变化 {}，预计用时 {}";
+    PlanUnknown => "unknown", "未知";
+    ConfirmStart => "Start downloading and updating?", "开始下载并更新？";
+
+    // 下载
+    DownloadCancelled => "download cancelled", "下载已取消";
+    ResumeUnsupported => "⚠️ Server does not support resuming, restarting the download", "⚠️ 服务器不支持断点续传，重新下载";
+    DownloadDone => "✅ Download complete", "✅ 下载完成";
+    DownloadRetry => "⚠️ Download failed (attempt {})", "⚠️ 下载失败（第 {} 次尝试）";
+    RetriesExceeded => "max retries exceeded: {}", "超过最大重试次数: {}";
+    LineParseFailed => "⚠️ Failed to parse line {}", "⚠️ 第 {} 行解析失败";
+    DownloadUrl => "📥 Download URL", "📥 下载链接";
+    Downloading => "⬇️ Downloading...", "⬇️ 正在下载...";
+    DownloadFailedNext => "⚠️ Download failed, trying the next URL", "⚠️ 下载失败，尝试下一个地址";
+    ChecksumFailedNext => "⚠️ Checksum mismatch, trying the next URL", "⚠️ 校验失败，尝试下一个地址";
+    NoDownloadUrl => "no download URL available", "没有可用的下载地址";
+
+    // 应用
+    PackageConflict => "conflicting packages: {} appears in both {} and {}", "更新包冲突: {} 同时出现在 {} 和 {}";
+    Extracting => "📦 Extracting...", "📦 正在解压...";
+    SkipMissingFile => "⚠️ Skipping missing file", "⚠️ 跳过不存在文件";
+    PatchFailed => "❌ Patch failed", "❌ 补丁失败";
+    Deleting => "🗑️ Deleting", "🗑️ 正在删除";
+    Placing => "📁 Placing updated files...", "📁 正在放置更新文件...";
+    PlaceRetry => "⚠️ Failed to place file, retrying", "⚠️ 放置失败，重试";
+    PlaceSkipped => "⚠️ Failed to place file, skipping", "⚠️ 放置失败，跳过";
+    PlaceDone => "📁 Files placed", "📁 文件放置完成";
+    CleaningUp => "🧹 Cleaning up temporary files...", "🧹 清理临时文件...";
+
+    // 错误
+    ErrApi => "API request failed: {}: {}", "API 请求失败: {}: {}";
+    ErrApiRetcode => "API returned an error (retcode {}): {}", "API 返回错误（retcode {}）: {}";
+    ErrSchema => "Unexpected API response at field `{}`: {}", "API 响应格式不符，字段 `{}`: {}";
+    ErrManifest => "Invalid Sophon manifest: {}: {}", "Sophon 清单无效: {}: {}";
+    ErrDownload => "Download failed: {}: {}", "下载失败: {}: {}";
+    ErrChecksum => "Checksum mismatch: {} (expected {}, got {})", "校验失败: {}（期望 {}，实际 {}）";
+    ErrExtract => "Extraction failed: {}: {}", "解压失败: {}: {}";
+    ErrPatch => "Patch failed: {} (exit code {}): {}", "补丁失败: {}（退出码 {}）: {}";
+    ErrNoExitCode => "none", "无";
+    ErrDelete => "Delete failed: {}: {}", "删除失败: {}: {}";
+    ErrCopy => "Copy failed: {}: {}", "复制失败: {}: {}";
+    ErrConfig => "Config error: {}: {}", "配置错误: {}: {}";
+    ErrIo => "File operation failed: {}: {}", "文件操作失败: {}: {}";
+    ErrPlan => "Cannot plan the update: {}", "无法制定更新计划: {}";
+    ErrCancelled => "Cancelled: {}", "操作已取消: {}";
+    ErrVerify => "Verification failed after the update: {} files missing, {} corrupted", "更新后校验失败: {} 个文件缺失，{} 个文件损坏";
+
+    // 更新器
+    UsingCachedManifest => "📴 Using the cached manifest", "📴 使用缓存的清单";
+    OfflineNoManifest => "no cached manifest for offline mode", "离线模式下没有缓存的清单";
+    FetchingManifest => "Fetching update info", "获取更新信息";
+    ManifestNotModified => "Manifest unchanged, using the cache", "清单未变化，使用缓存";
+    ManifestCacheFailed => "⚠️ Failed to cache the manifest", "⚠️ 无法缓存清单";
+    MissingData => "missing data", "缺少数据";
+    NoGamePackages => "no game packages", "没有游戏包";
+    InvalidVersion => "invalid version: {}", "无效的版本号: {}";
+    NoPatchFrom => "no patch from {}", "没有从 {} 升级的补丁";
+    PatchNoGamePkg => "the {} patch has no game package", "{} 的补丁中没有游戏包";
+    PatchNoLanguage => "the {} patch has no {} voice pack", "{} 的补丁中没有语言 {}";
+    NotOlderThanLatest => "installed version {} is not older than the latest {}", "已安装版本 {} 不低于最新版本 {}";
+    UnknownPatchVersion => "⚠️ Unrecognized patch version", "⚠️ 无法识别补丁的版本号";
+    NoFullPackage => "no full {} package in the manifest", "清单中没有 {} 的完整安装包";
+    FullNoLanguage => "the full {} package has no {} voice pack", "{} 的完整安装包中没有语言 {}";
+    InvalidPackagePath => "invalid package path: {}", "无效的更新包路径: {}";
+    PackageInManifest => "Found the package in the manifest", "已在清单中找到更新包";
+    PackageNotInManifest => "⚠️ Package not in the manifest, skipping the md5 check", "⚠️ 清单中没有该更新包，跳过 md5 校验";
+    PackageIncomplete => "package missing or incomplete: {}", "更新包不存在或不完整: {}";
+    ThroughputFailed => "⚠️ Failed to record the download speed", "⚠️ 无法记录下载速度";
+    StagingContainsGameDir => "the staging dir must not contain the game dir", "暂存目录不能包含游戏目录";
+    UnknownTargetVersion => "⚠️ Unknown target version, config.ini not updated", "⚠️ 未知目标版本，不更新 config.ini";
+    CacheEvicted => "🧹 Evicted cached packages", "🧹 已淘汰缓存的更新包";
+    CachePruneFailed => "⚠️ Failed to prune the package cache", "⚠️ 无法清理更新包缓存";
+    JoiningVolumes => "Joining split package volumes", "拼接分卷安装包";
+    SophonNeedsNetwork => "Sophon updates need network access", "Sophon 更新需要联网";
+    FetchingSophon => "Fetching Sophon info", "获取 Sophon 信息";
+    NoGameBranch => "no game branch", "没有游戏分支";
+    SophonBuild => "Sophon build", "Sophon 构建版本";
+    SophonNoManifest => "build {} has no {} manifest", "构建 {} 中没有 {} 的清单";
+    SophonPlan => "Sophon update plan", "Sophon 更新计划";
+    ZstdFailed => "zstd decompression failed: {}", "zstd 解压失败: {}";
+    ChunkFailedNext => "⚠️ Chunk download failed, trying the next URL", "⚠️ 分块下载失败，尝试下一个地址";
+    ManifestInvalidValue => "invalid {}: {}", "{} 无效: {}";
+    ManifestPathEscapes => "file path is outside the game dir", "文件路径不在游戏目录内";
+    ManifestChunkOverflow => "chunk range overflows", "分块范围溢出";
+    ManifestChunkName => "chunk name is not a plain file name", "分块名称不是单个文件名";
+    FileSizeMismatch => "⚠️ File size mismatch", "⚠️ 文件大小不符";
+    FileMismatch => "⚠️ File size or md5 mismatch", "⚠️ 文件大小或 md5 不符";
+    FileMissing => "⚠️ File missing", "⚠️ 文件缺失";
+    RepairNoResListUrl => "no resource URL in the manifest, cannot repair", "清单中没有资源地址，无法修复";
+    RepairNeedsNetwork => "repair needs network access", "修复需要联网";
+    NotInPkgVersion => "{} is not in pkg_version", "{} 不在 pkg_version 中";
+    Repaired => "🩹 Repaired", "🩹 已修复";
+    RepairFailed => "⚠️ Repair failed", "⚠️ 修复失败";
+    OrphansNeedPkgVersion => "{} has no pkg_version, cannot tell which files are orphaned", "{} 中没有 pkg_version，无法判断残留文件";
+    RestoreModesFailed => "⚠️ Failed to restore file permissions", "⚠️ 无法恢复文件权限";
+
+    // 调试日志
+    SearchingInstalls => "Searching for game installs", "搜索游戏安装";
+    OrphanDeleted => "Deleted orphaned file", "删除残留文件";
+    CacheEntryEvicted => "Evicted cached package", "淘汰缓存的更新包";
+    DirModeRelaxed => "Temporarily relaxed directory permissions", "临时放宽目录权限";
+    OwnerChanged => "Changed owner", "修改属主";
+    RequestSent => "Sending download request", "发起下载请求";
+    FileAssembled => "File assembled", "文件组装完成";
+    HpatchzDone => "hpatchz finished", "hpatchz 完成";
+    FilePlaced => "Placed file", "放置文件";
+    CachedPatchFound => "Usable older patch found in the cache", "缓存中有可用的旧补丁";
+    SkipUnlisted => "Not in pkg_version, skipping verification", "不在 pkg_version 中，跳过校验";
+    ServerResponded => "Server responded", "服务器已响应";
+    Md5Verified => "md5 verified", "md5 校验通过";
+    PackageExists => "Package already downloaded", "更新包已存在";
+
+    // 进度条
+    TplExtract => "📦 Extracting {wide_bar} {pos}/{len} {msg}", "📦 解压中 {wide_bar} {pos}/{len} {msg}";
+    PatchPrefix => "🔧 Patching", "🔧 补丁中";
+    TplDelete => "🗑️ Deleting {wide_bar} {pos}/{len} {msg}", "🗑️ 删除中 {wide_bar} {pos}/{len} {msg}";
+    TplCopy => "📄 Placing {wide_bar} {pos}/{len} {msg}", "📄 放置中 {wide_bar} {pos}/{len} {msg}";
+    TplAssemble => "🧩 Assembling {wide_bar} {pos}/{len} {msg}", "🧩 组装中 {wide_bar} {pos}/{len} {msg}";
+    TplCleanup => "🧹 Cleaning up {spinner} {msg}", "🧹 清理中 {spinner} {msg}";
+    TplVerify => "🔍 Verifying {wide_bar} {pos}/{len} {msg}", "🔍 校验中 {wide_bar} {pos}/{len} {msg}";
+    TplRepair => "🩹 Repairing {wide_bar} {pos}/{len} {msg}", "🩹 修复中 {wide_bar} {pos}/{len} {msg}";
+    DoneExtract => "📦 Extracted", "📦 解压完成";
+    DonePatch => "🔧 Patched", "🔧 补丁完成";
+    DoneDelete => "🗑️ Deleted", "🗑️ 删除完成";
+    DoneCopy => "📄 Files placed", "📄 文件放置完成";
+    DoneAssemble => "🧩 Files assembled", "🧩 文件组装完成";
+    DoneCleanup => "🧹 Cleaned up", "🧹 清理完成";
+    DoneVerify => "🔍 Verified", "🔍 校验完成";
+    DoneRepair => "🩹 Repaired", "🩹 修复完成";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_parse_and_format() {
+        assert_eq!(Locale::parse("zh_CN.UTF-8"), Some(Locale::ZhCn));
+        assert_eq!(Locale::parse("en_US.UTF-8"), Some(Locale::En));
+        assert_eq!(Locale::parse("C"), Some(Locale::En));
+        assert_eq!(Locale::parse("fr_FR"), None);
+
+        assert_eq!(Msg::VerifyFound.text_in(Locale::En), "⚠️ Verification found {} missing and {} corrupted files");
+        assert_eq!(format(Msg::VerifyFound.text_in(Locale::ZhCn), &[&1, &2]), "⚠️ 校验发现 1 个缺失、2 个损坏的文件");
+        assert_eq!(format("{wide_bar} {}", &[&"a"]), "{wide_bar} a");
+    }
+}
@@ -0,0 +1,247 @@
+//! 交互式选择：方向键选择游戏、起始版本与语言，下载前确认；无法交互时使用命令行参数或默认值
+
+use std::path::PathBuf;
+use dialoguer::console::Term;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Input, MultiSelect, Select};
+use indicatif::{HumanBytes, HumanDuration};
+use tracing::{info, warn};
+use genshin_impact_updater::discover::{discover, Install, Region};
+use genshin_impact_updater::language::{language_name, select_audio_pkgs};
+use genshin_impact_updater::parser::{AudioPkg, GamePackage, Patch};
+use genshin_impact_updater::{t, Error, PackageKind, PlanSummary, Result, SummaryRow, UpgradePath};
+
+fn term_err(e: dialoguer::Error) -> Error {
+    Error::Cancelled(t!(TermFailed, e))
+}
+
+fn cancelled() -> Error {
+    Error::Cancelled(t!(SelectionCancelled).to_string())
+}
+
+/// 指定了 `--yes` 或 stderr 不是终端（脚本、定时任务、`--json`）时不弹出选择
+pub fn unattended(yes: bool) -> bool {
+    yes || !Term::stderr().is_term()
+}
+
+/// 找到的安装在列表中的显示文本
+pub fn install_label(install: &Install) -> String {
+    let region = match install.region {
+        Region::Global => t!(RegionGlobal),
+        Region::China => t!(RegionChina),
+    };
+    format!(
+        "{}  [{}] {} {}",
+        install.game_dir.display(),
+        install.launcher,
+        region,
+        install.version.as_deref().unwrap_or(t!(VersionUnknown)),
+    )
+}
+
+/// 从 Wine 前缀中找到的安装里选择游戏目录，没有找到或选择手动输入时输入目录；
+/// `unattended` 时只接受唯一找到的安装，`destructive`（会删除文件的命令）时要求用 `--game-dir` 指定
+pub fn game_dir(unattended: bool, destructive: bool) -> Result<PathBuf> {
+    if unattended && destructive {
+        return Err(Error::Plan(t!(GameDirRequiredToDelete).to_string()));
+    }
+    let installs = discover();
+    if unattended {
+        return match installs.as_slice() {
+            [install] => {
+                info!("{}", t!(UsingInstall, install_label(install)));
+                Ok(install.game_dir.clone())
+            }
+            _ => Err(Error::Plan(t!(GameDirRequired, installs.len()))),
+        };
+    }
+    if !installs.is_empty() {
+        let mut items: Vec<String> = installs.iter().map(install_label).collect();
+        items.push(t!(EnterManually).to_string());
+        let idx = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(t!(PromptInstall))
+            .items(&items)
+            .default(0)
+            .interact_on_opt(&Term::stderr())
+            .map_err(term_err)?
+            .ok_or_else(cancelled)?;
+        if let Some(install) = installs.get(idx) {
+            return Ok(install.game_dir.clone());
+        }
+    }
+
+    input_game_dir()
+}
+
+/// 输入游戏目录，必须是已存在的目录
+fn input_game_dir() -> Result<PathBuf> {
+    let input: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(t!(PromptGameDir))
+        .validate_with(|input: &String| {
+            if PathBuf::from(input.trim()).is_dir() {
+                Ok(())
+            } else {
+                Err(t!(NotADirectory))
+            }
+        })
+        .interact_text_on(&Term::stderr())
+        .map_err(term_err)?;
+    Ok(PathBuf::from(input.trim()))
+}
+
+/// 按 `wanted`（biz 或 id）选择游戏，未指定且清单中有多个游戏时交互选择
+pub fn select_game<'a>(game_packages: &'a [GamePackage], wanted: Option<&str>, unattended: bool) -> Result<&'a GamePackage> {
+    if let Some(wanted) = wanted {
+        return game_packages
+            .iter()
+            .find(|pkg| pkg.game.biz.eq_ignore_ascii_case(wanted) || pkg.game.id == wanted)
+            .ok_or_else(|| Error::Plan(t!(UnknownGame, wanted)));
+    }
+    if game_packages.len() <= 1 {
+        return game_packages.first().ok_or_else(|| Error::Plan(t!(NoGame).to_string()));
+    }
+    if unattended {
+        return Err(Error::Plan(t!(GameRequired).to_string()));
+    }
+
+    let items: Vec<String> = game_packages
+        .iter()
+        .map(|pkg| format!("{} ({}) {}", pkg.game.biz, pkg.game.id, pkg.main.major.version))
+        .collect();
+    let idx = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(t!(PromptGame))
+        .items(&items)
+        .default(0)
+        .interact_on_opt(&Term::stderr())
+        .map_err(term_err)?
+        .ok_or_else(cancelled)?;
+    Ok(&game_packages[idx])
+}
+
+/// 无法确定已安装版本时选择起始版本
+pub fn select_patch(patches: &[Patch]) -> Result<&Patch> {
+    let items: Vec<String> = patches
+        .iter()
+        .map(|patch| {
+            let size: u64 = patch.game_pkgs.iter().map(|pkg| pkg.size).sum();
+            format!("{}  {}", patch.version, t!(GamePkgSize, HumanBytes(size)))
+        })
+        .collect();
+
+    let idx = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(t!(PromptFromVersion))
+        .items(&items)
+        .default(0)
+        .interact_on_opt(&Term::stderr())
+        .map_err(term_err)?
+        .ok_or_else(cancelled)?;
+    Ok(&patches[idx])
+}
+
+/// 勾选要更新的语音包，默认勾选已安装的语言；不更新已安装的语言时需要确认
+///
+/// 指定了 `wanted` 时直接使用，`unattended` 时使用已安装的语言，均不再询问
+pub fn select_languages(audio_pkgs: &[AudioPkg], installed: &[String], wanted: &[String], unattended: bool) -> Result<Vec<String>> {
+    if !wanted.is_empty() || unattended {
+        let selection = select_audio_pkgs(audio_pkgs, if wanted.is_empty() { installed } else { wanted }, installed);
+        if !wanted.is_empty() && !selection.unknown.is_empty() {
+            return Err(Error::Plan(t!(UnknownLanguages, selection.unknown.join(" "))));
+        }
+        if !selection.missing.is_empty() {
+            warn!("{}", t!(MissingLanguages, selection.missing.join(" ")));
+        }
+        return Ok(selection.selected.iter().map(|pkg| pkg.language.clone()).collect());
+    }
+
+    let items: Vec<(String, bool)> = audio_pkgs
+        .iter()
+        .map(|pkg| {
+            let name = language_name(&pkg.language).unwrap_or(&pkg.language);
+            let checked = installed.iter().any(|code| code.eq_ignore_ascii_case(&pkg.language));
+            (format!("{} {}  {}", pkg.language, name, HumanBytes(pkg.size)), checked)
+        })
+        .collect();
+
+    loop {
+        let chosen = MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(t!(PromptLanguages))
+            .items_checked(&items)
+            .interact_on_opt(&Term::stderr())
+            .map_err(term_err)?
+            .ok_or_else(cancelled)?;
+
+        let wanted: Vec<String> = chosen.iter().map(|&idx| audio_pkgs[idx].language.clone()).collect();
+        let selection = select_audio_pkgs(audio_pkgs, &wanted, installed);
+        if !selection.missing.is_empty()
+            && !confirm(&t!(ConfirmMissingLanguages, selection.missing.join(" ")), false)?
+        {
+            continue;
+        }
+
+        return Ok(wanted);
+    }
+}
+
+/// 显示升级路线、各更新包的大小与缓存情况以及预计的磁盘变化与下载时间，`yes` 时不再确认
+pub fn confirm_plan(path: &UpgradePath, summary: &PlanSummary, yes: bool) -> Result<bool> {
+    eprintln!();
+    eprintln!("{}", path.reason);
+    eprintln!(
+        "  {:<16} {:<8} {:>10} {:>10} {:>12}",
+        "",
+        t!(PlanPackage),
+        t!(PlanSize),
+        t!(PlanCached),
+        t!(PlanDecompressed),
+    );
+    for row in &summary.rows {
+        let from = if row.from_version.is_empty() { "?" } else { &row.from_version };
+        let name = match &row.kind {
+            PackageKind::Game => "game",
+            PackageKind::Audio(language) => language,
+        };
+        eprintln!(
+            "  {:<16} {:<8} {:>10} {:>10} {:>12}",
+            format!("{} → {}", from, row.to_version),
+            name,
+            HumanBytes(row.size).to_string(),
+            HumanBytes(row.cached).to_string(),
+            HumanBytes(row.decompressed_size).to_string(),
+        );
+    }
+    let sum = |field: fn(&SummaryRow) -> u64| HumanBytes(summary.rows.iter().map(field).sum()).to_string();
+    eprintln!(
+        "  {:<16} {:<8} {:>10} {:>10} {:>12}",
+        t!(PlanTotal),
+        "",
+        sum(|row| row.size),
+        sum(|row| row.cached),
+        sum(|row| row.decompressed_size),
+    );
+
+    let disk_change = match summary.disk_change {
+        Some(change) if change < 0 => format!("-{}", HumanBytes(change.unsigned_abs())),
+        Some(change) => format!("+{}", HumanBytes(change as u64)),
+        None => t!(PlanUnknown).to_string(),
+    };
+    let eta = summary.eta.map_or_else(|| t!(PlanUnknown).to_string(), |eta| HumanDuration(eta).to_string());
+    eprintln!("{}", t!(PlanEstimate, HumanBytes(summary.download), disk_change, eta));
+
+    if yes {
+        return Ok(true);
+    }
+    confirm(t!(ConfirmStart), true)
+}
+
+/// 是/否确认，按 Esc 视为否；没有终端时报错，提示使用 `--yes`
+pub fn confirm(prompt: &str, default: bool) -> Result<bool> {
+    if !Term::stderr().is_term() {
+        return Err(Error::Cancelled(t!(ConfirmNeedsYes).to_string()));
+    }
+    let answer = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(default)
+        .interact_on_opt(&Term::stderr())
+        .map_err(term_err)?;
+    Ok(answer.unwrap_or(false))
+}
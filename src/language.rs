@@ -0,0 +1,181 @@
+use std::fs;
+use std::path::Path;
+use crate::error::{IoContext, Result};
+
+use crate::parser::AudioPkg;
+
+/// API 语言代码与游戏目录中语音包名称的对应关系
+const VOICE_LANGUAGES: &[(&str, &str)] = &[
+    ("zh-cn", "Chinese"),
+    ("en-us", "English(US)"),
+    ("ja-jp", "Japanese"),
+    ("ko-kr", "Korean"),
+];
+
+/// 游戏数据目录（国际服 / 国服）
+pub(crate) const DATA_DIRS: &[&str] = &["GenshinImpact_Data", "YuanShen_Data"];
+
+/// 是否为已知的 API 语言代码
+pub fn is_language_code(code: &str) -> bool {
+    VOICE_LANGUAGES.iter().any(|(c, _)| c.eq_ignore_ascii_case(code))
+}
+
+/// 将游戏目录中的语音包名称（如 `English(US)`）转换为 API 语言代码
+pub fn language_code(name: &str) -> Option<&'static str> {
+    VOICE_LANGUAGES
+        .iter()
+        .find(|(_, dir_name)| dir_name.eq_ignore_ascii_case(name.trim()))
+        .map(|(code, _)| *code)
+}
+
+/// 将 API 语言代码转换为语音包名称（如 `English(US)`）
+pub fn language_name(code: &str) -> Option<&'static str> {
+    VOICE_LANGUAGES
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code))
+        .map(|(_, name)| *name)
+}
+
+/// 已安装语音包的检测结果
+#[derive(Debug, Default, PartialEq)]
+pub struct InstalledLanguages {
+    /// 已识别的语言代码
+    pub codes: Vec<String>,
+    /// 无法识别的语音包名称
+    pub unknown: Vec<String>,
+}
+
+/// 从游戏目录检测已安装的语音包
+/// 同时读取 `Audio_*_pkg_version` 文件与 `Persistent/audio_lang_14`
+pub fn detect_installed_languages(game_dir: &Path) -> Result<InstalledLanguages> {
+    let mut names = Vec::new();
+
+    if game_dir.is_dir() {
+        for entry in fs::read_dir(game_dir).with_path(game_dir)? {
+            let file_name = entry.with_path(game_dir)?.file_name();
+            let file_name = file_name.to_string_lossy();
+            if let Some(name) = file_name
+                .strip_prefix("Audio_")
+                .and_then(|rest| rest.strip_suffix("_pkg_version"))
+            {
+                names.push(name.to_string());
+            }
+        }
+    }
+
+    for data_dir in DATA_DIRS {
+        let lang_file = game_dir.join(data_dir).join("Persistent").join("audio_lang_14");
+        if lang_file.is_file() {
+            let data = fs::read_to_string(&lang_file).with_path(&lang_file)?;
+            names.extend(
+                data.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string),
+            );
+        }
+    }
+
+    let mut installed = InstalledLanguages::default();
+    for name in names {
+        match language_code(&name) {
+            Some(code) => {
+                if !installed.codes.iter().any(|c| c == code) {
+                    installed.codes.push(code.to_string());
+                }
+            }
+            None => {
+                if !installed.unknown.contains(&name) {
+                    installed.unknown.push(name);
+                }
+            }
+        }
+    }
+    installed.codes.sort_by_key(|code| VOICE_LANGUAGES.iter().position(|(c, _)| c == code));
+
+    Ok(installed)
+}
+
+/// 语言选择结果
+#[derive(Debug)]
+pub struct LanguageSelection<'a> {
+    pub selected: Vec<&'a AudioPkg>,
+    /// 输入了但更新包中不存在的语言
+    pub unknown: Vec<String>,
+    /// 已安装但不会被更新的语言
+    pub missing: Vec<String>,
+}
+
+/// 根据输入的语言代码挑选语音包，并找出未知语言与被遗漏的已安装语言
+pub fn select_audio_pkgs<'a>(
+    audio_pkgs: &'a [AudioPkg],
+    wanted: &[String],
+    installed: &[String],
+) -> LanguageSelection<'a> {
+    let selected: Vec<&AudioPkg> = audio_pkgs
+        .iter()
+        .filter(|pkg| wanted.iter().any(|w| w.eq_ignore_ascii_case(&pkg.language)))
+        .collect();
+
+    let unknown = wanted
+        .iter()
+        .filter(|w| !audio_pkgs.iter().any(|pkg| pkg.language.eq_ignore_ascii_case(w)))
+        .cloned()
+        .collect();
+
+    let missing = installed
+        .iter()
+        .filter(|code| !selected.iter().any(|pkg| pkg.language.eq_ignore_ascii_case(code)))
+        .cloned()
+        .collect();
+
+    LanguageSelection { selected, unknown, missing }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn audio_pkg(language: &str) -> AudioPkg {
+        AudioPkg {
+            language: language.to_string(),
+            url: format!("http://example.com/{}.zip", language),
+            md5: String::new(),
+            size: 0,
+            decompressed_size: 0,
+        }
+    }
+
+    #[test]
+    fn test_detect_installed_languages() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join("Audio_English(US)_pkg_version"), "").unwrap();
+        fs::write(root.join("Audio_Japanese_pkg_version"), "").unwrap();
+
+        let persistent = root.join("GenshinImpact_Data").join("Persistent");
+        fs::create_dir_all(&persistent).unwrap();
+        fs::write(persistent.join("audio_lang_14"), "Chinese\r\nEnglish(US)\r\nKlingon\r\n").unwrap();
+
+        let installed = detect_installed_languages(root).unwrap();
+        assert_eq!(installed.codes, vec!["zh-cn", "en-us", "ja-jp"]);
+        assert_eq!(installed.unknown, vec!["Klingon"]);
+    }
+
+    #[test]
+    fn test_select_audio_pkgs() {
+        let pkgs = vec![audio_pkg("zh-cn"), audio_pkg("en-us"), audio_pkg("ja-jp")];
+        let wanted = vec!["EN-US".to_string(), "fr-fr".to_string()];
+        let installed = vec!["en-us".to_string(), "ja-jp".to_string(), "ko-kr".to_string()];
+
+        let selection = select_audio_pkgs(&pkgs, &wanted, &installed);
+        assert_eq!(
+            selection.selected.iter().map(|pkg| pkg.language.as_str()).collect::<Vec<_>>(),
+            vec!["en-us"]
+        );
+        assert_eq!(selection.unknown, vec!["fr-fr"]);
+        assert_eq!(selection.missing, vec!["ja-jp", "ko-kr"]);
+    }
+}
@@ -0,0 +1,33 @@
+//! 原神更新器核心库：获取更新清单、制定计划、下载、应用与校验
+
+pub mod error;
+pub mod util;
+pub mod parser;
+pub mod language;
+pub mod game_config;
+pub mod logging;
+pub mod progress;
+pub mod pkg_version;
+pub mod updater;
+pub mod manifest_cache;
+pub mod settings;
+pub mod sophon;
+pub mod orphans;
+pub mod package_cache;
+pub mod staging;
+pub mod i18n;
+pub mod discover;
+pub mod permissions;
+pub mod version;
+pub mod throughput;
+#[cfg(test)]
+mod testing;
+
+pub use error::{Error, Result};
+pub use progress::{Event, Phase, ProgressSink};
+pub use updater::{PackageKind, PlanSummary, PlannedPackage, SummaryRow, UpdatePlan, Updater, VerifyReport};
+pub use version::{GameVersion, Route, UpgradePath};
+
+pub const API_URL: &str = "https://sg-hyp-api.hoyoverse.com/hyp/hyp-connect/api/getGamePackages?game_ids[]=gopR6Cufr3&launcher_id=VYTpXlbWo8";
+pub const UPDATE_DIR: &str = "updates";
+pub const CACHE_DIR: &str = "cache";
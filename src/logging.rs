@@ -0,0 +1,96 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::error::{IoContext, Result};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+
+pub const LOG_DIR: &str = "logs";
+/// 保留的历史日志文件数量
+const MAX_LOG_FILES: usize = 10;
+
+/// 根据 `-v`/`-q` 计算终端输出级别
+fn console_level(verbose: u8, quiet: bool) -> LevelFilter {
+    if quiet {
+        return LevelFilter::WARN;
+    }
+    match verbose {
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+/// 删除多余的旧日志，只保留最新的 `keep` 个
+fn rotate_logs(log_dir: &Path, keep: usize) -> Result<()> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(log_dir).with_path(log_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "log"))
+        .collect();
+
+    // 文件名包含时间戳，按名称排序即按时间排序
+    logs.sort();
+    if logs.len() > keep {
+        for old in &logs[..logs.len() - keep] {
+            fs::remove_file(old).with_path(old)?;
+        }
+    }
+    Ok(())
+}
+
+/// 初始化日志：终端按 `-v`/`-q` 过滤，同时写入本次运行的日志文件（DEBUG 级别）
+/// 返回日志文件路径
+pub fn init(verbose: u8, quiet: bool) -> Result<PathBuf> {
+    fs::create_dir_all(LOG_DIR).with_path(LOG_DIR)?;
+    // 为本次运行留出位置
+    rotate_logs(Path::new(LOG_DIR), MAX_LOG_FILES - 1)?;
+
+    let log_path = Path::new(LOG_DIR).join(format!(
+        "updater-{}.log",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let log_file = File::create(&log_path).with_path(&log_path)?;
+
+    let console_layer = fmt::layer()
+        .with_writer(std::io::stderr)
+        .without_time()
+        .with_target(false)
+        .with_filter(console_level(verbose, quiet));
+
+    let file_layer = fmt::layer()
+        .with_writer(Mutex::new(log_file))
+        .with_ansi(false)
+        .with_filter(LevelFilter::DEBUG);
+
+    tracing_subscriber::registry()
+        .with(console_layer)
+        .with(file_layer)
+        .init();
+
+    Ok(log_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rotate_logs_keeps_newest() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["updater-20240101-000000.log", "updater-20240102-000000.log", "updater-20240103-000000.log", "notes.txt"] {
+            fs::write(temp_dir.path().join(name), "").unwrap();
+        }
+
+        rotate_logs(temp_dir.path(), 2).unwrap();
+
+        let mut left: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        left.sort();
+        assert_eq!(left, vec!["notes.txt", "updater-20240102-000000.log", "updater-20240103-000000.log"]);
+    }
+}
@@ -1,103 +1,392 @@
-mod util;
-mod parser;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+use clap::{Parser, Subcommand};
+use indicatif::HumanBytes;
+use tracing::{error, info, warn};
+use chrono::{DateTime, Local};
+use genshin_impact_updater::{discover, i18n, logging, settings, t, Error, GameVersion, Result, Updater, CACHE_DIR};
+use genshin_impact_updater::package_cache::PackageCache;
+use genshin_impact_updater::permissions::Owner;
+use genshin_impact_updater::settings::{CopyErrorPolicy, Settings};
+use genshin_impact_updater::progress::{BarSink, Event, JsonSink, ProgressSink};
+use genshin_impact_updater::util::UpdateStats;
 
-use std::{fs, path::Path, process::Command, io::Cursor};
-use anyhow::{Result, anyhow};
-use serde::Deserialize;
-use std::fs::File;
-use std::io::{self, Write};
-// use fs_extra::dir::DirEntryAttr::Path;
-use crate::util::*;
-use crate::parser::*;
+mod interactive;
 
-const API_URL: &str = "https://sg-hyp-api.hoyoverse.com/hyp/hyp-connect/api/getGamePackages?game_ids[]=gopR6Cufr3&launcher_id=VYTpXlbWo8";
-const UPDATE_DIR: &str = "updates";
-const UNPACK_DIR: &str = "unpacked";
-const UNPACKER_PATH: &str = "./Genshin-Impact-New-Music-Unpacker";
+// 帮助文本取自文本目录，随 `LANG` 切换语言
+#[derive(Debug, Parser)]
+#[command(version, about = t!(AppAbout), long_about = None)]
+struct Cli {
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet", help = t!(ArgVerbose))]
+    verbose: u8,
+    #[arg(short, long, help = t!(ArgQuiet))]
+    quiet: bool,
+    #[arg(long, help = t!(ArgJson))]
+    json: bool,
+    #[arg(long, help = t!(ArgOffline))]
+    offline: bool,
+    #[arg(long, global = true, help = t!(ArgRepair))]
+    repair: bool,
+    #[arg(long, value_enum, value_name = "POLICY", help = t!(ArgOnCopyError))]
+    on_copy_error: Option<CopyErrorPolicy>,
+    #[arg(long, value_name = "N", help = t!(ArgCopyRetries))]
+    copy_retries: Option<u8>,
+    #[arg(long, value_name = "USER[:GROUP]", help = t!(ArgOwner))]
+    owner: Option<Owner>,
+    #[arg(long, help = t!(ArgSequential))]
+    sequential: bool,
+    #[arg(long, value_name = "PATH", help = t!(ArgConfig))]
+    config: Option<PathBuf>,
+    #[arg(long, value_name = "DIR", global = true, help = t!(ArgGameDir))]
+    game_dir: Option<PathBuf>,
+    #[arg(short, long, global = true, help = t!(ArgYes))]
+    yes: bool,
+    #[arg(long, value_name = "GAME", help = t!(ArgGame))]
+    game: Option<String>,
+    #[arg(long = "language", value_name = "LANG", help = t!(ArgLanguage))]
+    languages: Vec<String>,
 
-fn main() -> Result<()> {
-    println!("🚀 启动原神更新器...");
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    let mut game_root = String::new();
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(about = t!(CmdApply))]
+    Apply {
+        #[arg(long = "package", value_name = "ZIP", required = true, help = t!(ArgPackage))]
+        packages: Vec<PathBuf>,
+        #[arg(long, value_name = "VERSION", help = t!(ArgToVersion))]
+        to_version: Option<String>,
+    },
+    #[command(about = t!(CmdSophon))]
+    Sophon {
+        #[arg(long = "language", value_name = "LANG", help = t!(ArgLanguage))]
+        languages: Vec<String>,
+    },
+    #[command(about = t!(CmdCleanOrphans))]
+    CleanOrphans,
+    #[command(about = t!(CmdDiscover))]
+    Discover,
+    #[command(about = t!(CmdCache))]
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
 
-    println!("Enter Game Dir:");
-    io::stdin()
-        .read_line(&mut game_root)
-        .expect("input error.");
+#[derive(Debug, Subcommand)]
+enum CacheAction {
+    #[command(about = t!(CmdCacheList))]
+    List,
+    #[command(about = t!(CmdCachePrune))]
+    Prune {
+        #[arg(long, help = t!(ArgPruneAll))]
+        all: bool,
+    },
+}
 
-    let game_root = game_root.trim();
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let log_path = match logging::init(cli.verbose, cli.quiet) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("{}", t!(LogInitFailed, format!("{:#}", e)));
+            return ExitCode::FAILURE;
+        }
+    };
+    let progress: Arc<dyn ProgressSink> = if cli.json {
+        Arc::new(JsonSink::new())
+    } else {
+        Arc::new(BarSink::new())
+    };
 
-    // 1. 创建更新目录
+    info!(log = %log_path.display(), "{}", t!(Starting));
 
+    let started = Instant::now();
+    let mut stats = Vec::new();
+    let result = run(&cli, progress.clone(), &mut stats);
 
-    // 2. 获取最新安装包链接
-    let response = reqwest::blocking::get(API_URL)?.json::<Response>()?;
+    let skipped: Vec<&String> = stats.iter().flat_map(|s| &s.skipped).collect();
+    if !skipped.is_empty() {
+        warn!("{}", t!(SkippedFiles, skipped.len()));
+        for file in &skipped {
+            warn!("  {}", file);
+        }
+    }
 
-    println!("Latest Game id: {}", &response.data.game_packages[0].game.id);
-    println!("Latest Game version: {}", &response.data.game_packages[0].main.major.version);
+    if let Err(e) = &result {
+        error!(exit_code = e.exit_code(), "❌ {}", e);
+        progress.emit(Event::Error { message: e.to_string() });
+    }
+    progress.emit(Event::Summary {
+        success: result.is_ok(),
+        packages: stats.iter().map(|s| s.packages as usize).sum(),
+        patched: stats.iter().map(|s| s.patched).sum(),
+        deleted: stats.iter().map(|s| s.deleted).sum(),
+        copied: stats.iter().map(|s| s.copied).sum(),
+        skipped: stats.iter().map(|s| s.skipped.len() as u64).sum(),
+        elapsed_secs: started.elapsed().as_secs_f64(),
+    });
 
-    println!("Choose which do you want to upgrade from: ");
-    println!("  1) {}", &response.data.game_packages[0].main.patches[0].version);
-    println!("  2) {}", &response.data.game_packages[0].main.patches[1].version);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => ExitCode::from(e.exit_code()),
+    }
+}
 
-    let mut choice = String::new();
-    io::stdin()
-        .read_line(&mut choice)
-        .expect("Input error.");
+fn run(cli: &Cli, progress: Arc<dyn ProgressSink>, stats: &mut Vec<UpdateStats>) -> Result<()> {
+    let mut settings = settings::load(cli.config.as_deref())?;
+    if let Some(locale) = settings.ui.locale {
+        i18n::set_locale(locale);
+    }
+    if let Some(policy) = cli.on_copy_error {
+        settings.apply.on_copy_error = policy;
+    }
+    if let Some(retries) = cli.copy_retries {
+        settings.apply.copy_retries = retries;
+    }
+    // 缓存管理与查找安装不需要游戏目录
+    match &cli.command {
+        Some(Command::Cache { action }) => return run_cache(action, &settings),
+        Some(Command::Discover) => return run_discover(),
+        _ => {}
+    }
 
-    while let Err(_) = choice.trim().parse::<usize>() {
-        println!("Not a num.")
+    let game_dir = match &cli.game_dir {
+        Some(game_dir) => game_dir.clone(),
+        None => {
+            // 无人值守时不让删除文件的命令作用于自动找到的安装
+            let destructive = matches!(cli.command, Some(Command::Apply { .. } | Command::CleanOrphans));
+            interactive::game_dir(interactive::unattended(cli.yes), destructive)?
+        }
+    };
+
+    let updater = Updater::new(game_dir)
+        .with_settings(settings)
+        .with_progress(progress)
+        .with_offline(cli.offline)
+        .with_owner(cli.owner);
+
+    match &cli.command {
+        None => run_update(&updater, cli, stats),
+        Some(Command::Apply { packages, to_version }) => run_apply(&updater, packages, to_version.as_deref(), cli.repair, stats),
+        Some(Command::Sophon { languages }) => run_sophon(&updater, languages, cli.repair, stats),
+        Some(Command::CleanOrphans) => run_clean_orphans(&updater, cli.yes, stats),
+        Some(Command::Cache { .. } | Command::Discover) => unreachable!("handled before the game dir is resolved"),
     }
+}
 
-    let choice:usize = choice.trim().parse()?;
-    if choice != 1 && choice != 2 {
-        return Err(anyhow!("Not a choice."))
+/// 校验本次更新写入的文件，`repair` 时重新下载损坏的文件
+fn check_update(updater: &Updater, stats: &[UpdateStats], res_list_url: Option<&str>, repair: bool) -> Result<()> {
+    let mut report = updater.verify_touched(stats)?;
+    if report.is_ok() {
+        info!("{}", t!(Verified, report.checked));
+        return Ok(());
     }
+    warn!("{}", t!(VerifyFound, report.missing.len(), report.mismatched.len()));
 
-    let package = &response.data.game_packages[0].main.patches[choice - 1];
+    match res_list_url {
+        Some(res_list_url) if repair => report = updater.repair(&report, res_list_url)?,
+        _ if repair => warn!("{}", t!(NoResListUrl)),
+        _ => info!("{}", t!(RepairHint)),
+    }
 
-    let game_pkg = &package.game_pkgs[0];
+    if report.is_ok() {
+        return Ok(());
+    }
+    for file in report.missing.iter().chain(&report.mismatched) {
+        warn!(file = %file, "{}", t!(VerifyFailedFile));
+    }
+    Err(Error::Verify { missing: report.missing.len(), mismatched: report.mismatched.len() })
+}
 
-    println!("Chosen version: {}", package.version);
+/// 应用本地更新包，只使用缓存的清单校验 md5
+fn run_apply(updater: &Updater, packages: &[PathBuf], to_version: Option<&str>, repair: bool, stats: &mut Vec<UpdateStats>) -> Result<()> {
+    let response = updater.cached_manifest();
+    if response.is_none() {
+        warn!("{}", t!(NoCachedManifest));
+    }
 
-    let languages: Vec<String> = package.audio_pkgs
-        .iter()
-        .map(|audio_pkg| audio_pkg.language.clone())
-        .collect();
+    let mut plan = updater.plan_local(packages, response.as_ref())?;
+    if let Some(to_version) = to_version {
+        plan.to_version = to_version.to_string();
+    }
 
-    println!("Choose which language you want to upgrade({}): ", languages.join(" "));
+    updater.check_archives(&plan, packages)?;
+    stats.push(updater.apply(&plan, packages)?);
 
-    let mut choice = String::new();
-    io::stdin()
-        .read_line(&mut choice)
-        .expect("input error.");
+    let res_list_url = response
+        .as_ref()
+        .and_then(|response| response.game_package().ok())
+        .map(|game_package| game_package.main.major.res_list_url.as_str());
+    check_update(updater, stats, res_list_url, repair)?;
 
-    let choice: Vec<&str> = choice.trim().split_whitespace().collect();
+    info!("{}", t!(UpdateDone));
 
-    let mut audio_pkgs: Vec<&AudioPkg> = Vec::new();
+    Ok(())
+}
 
-    for audio_pkg in package.audio_pkgs.iter() {
-        if choice.contains(&audio_pkg.language.as_str()) {
-            audio_pkgs.push(audio_pkg)
+/// 通过 Sophon 分块下载更新
+fn run_sophon(updater: &Updater, languages: &[String], repair: bool, stats: &mut Vec<UpdateStats>) -> Result<()> {
+    let languages = if languages.is_empty() {
+        let installed = updater.installed_languages()?;
+        if !installed.unknown.is_empty() {
+            warn!("{}", t!(UnknownInstalledLanguages, installed.unknown.join(" ")));
         }
+        installed.codes
+    } else {
+        languages.to_vec()
+    };
+    info!("{}", t!(ChosenLanguage, languages.join(" ")));
+
+    let (version, sophon_stats) = updater.update_sophon(&languages)?;
+    stats.push(sophon_stats);
+
+    // Sophon 构建信息中没有资源地址，修复使用最新清单
+    let res_list_url = if repair {
+        Some(updater.fetch_manifest()?.game_package()?.main.major.res_list_url.clone())
+    } else {
+        None
+    };
+    check_update(updater, stats, res_list_url.as_deref(), repair)?;
+
+    info!("{}", t!(UpdateDoneVersion, version));
+
+    Ok(())
+}
+
+/// 列出或淘汰缓存的更新包
+fn run_cache(action: &CacheAction, settings: &Settings) -> Result<()> {
+    let cache = PackageCache::new(CACHE_DIR);
+    match action {
+        CacheAction::List => {
+            let entries = cache.list()?;
+            for entry in &entries {
+                let used_at: DateTime<Local> = entry.used_at.into();
+                info!("  {} {} ({}, {})", entry.md5, entry.name, HumanBytes(entry.size), used_at.format("%Y-%m-%d %H:%M"));
+            }
+            let total: u64 = entries.iter().map(|entry| entry.size).sum();
+            info!("{}", t!(CacheTotal, entries.len(), HumanBytes(total)));
+        }
+        CacheAction::Prune { all } => {
+            let removed = if *all { cache.clear()? } else { cache.prune(&settings.cache)? };
+            for entry in &removed {
+                info!("🗑️ {} {}", entry.md5, entry.name);
+            }
+            let freed: u64 = removed.iter().map(|entry| entry.size).sum();
+            info!("{}", t!(CachePruned, removed.len(), HumanBytes(freed)));
+        }
+    }
+    Ok(())
+}
+
+/// 列出各个 Wine 前缀中的游戏安装
+fn run_discover() -> Result<()> {
+    let installs = discover::discover();
+    if installs.is_empty() {
+        info!("{}", t!(DiscoverNone));
+        return Ok(());
+    }
+
+    for install in &installs {
+        info!("  {}", interactive::install_label(install));
+    }
+    info!("{}", t!(DiscoverTotal, installs.len()));
+
+    Ok(())
+}
+
+/// 删除残留文件，未指定 `--yes` 时先确认
+fn run_clean_orphans(updater: &Updater, yes: bool, stats: &mut Vec<UpdateStats>) -> Result<()> {
+    let orphans = updater.find_orphans()?;
+    if orphans.is_empty() {
+        info!("{}", t!(NoOrphans));
+        return Ok(());
+    }
+
+    for orphan in &orphans {
+        info!("  {} ({})", orphan.path, HumanBytes(orphan.size));
+    }
+    let total: u64 = orphans.iter().map(|orphan| orphan.size).sum();
+    info!("{}", t!(OrphanTotal, orphans.len(), HumanBytes(total)));
+
+    if !yes && !interactive::confirm(t!(ConfirmDeleteOrphans), false)? {
+        return Err(Error::Cancelled(t!(OrphansKept).to_string()));
+    }
+
+    let freed = updater.remove_orphans(&orphans)?;
+    stats.push(UpdateStats { deleted: orphans.len() as u64, ..UpdateStats::default() });
+    info!("{}", t!(Freed, HumanBytes(freed)));
+
+    Ok(())
+}
+
+/// 交互式在线更新；`--yes` 或没有终端时使用 `--game`、`--language` 与检测到的默认值，不再询问
+fn run_update(updater: &Updater, cli: &Cli, stats: &mut Vec<UpdateStats>) -> Result<()> {
+    let unattended = interactive::unattended(cli.yes);
+
+    // 获取最新安装包链接
+    let response = updater.fetch_manifest()?;
+    let game_package = interactive::select_game(response.game_packages(), cli.game.as_deref(), unattended)?;
+
+    info!("{}", t!(LatestGameId, game_package.game.id));
+    info!("{}", t!(LatestGameVersion, game_package.main.major.version));
+
+    let patches = &game_package.main.patches;
+    let latest: GameVersion = game_package.main.major.version.parse()?;
+    let installed_version = match updater.installed_version()? {
+        Some(version) => {
+            info!("{}", t!(InstalledVersion, version));
+            version.parse::<GameVersion>().map_err(|e| warn!("⚠️ {}", e)).ok()
+        }
+        None => None,
+    };
+    if installed_version.is_some_and(|version| version >= latest) {
+        info!("{}", t!(AlreadyLatest));
+        return Ok(());
     }
 
-    println!("Chosen language: {}",
-             audio_pkgs
-                 .iter()
-                 .map(|pkg| pkg.language.clone())
-                 .collect::<Vec<_>>()
-                 .join(" "));
+    // 无法确定已安装版本时手动选择起始版本，无法交互或清单中没有补丁时只能下载完整安装包
+    let from_version = match installed_version {
+        Some(version) => Some(version),
+        None if !patches.is_empty() && !unattended => {
+            let package = interactive::select_patch(patches)?;
+            info!("{}", t!(ChosenVersion, package.version));
+            Some(package.version.parse()?)
+        }
+        None => None,
+    };
 
-    ensure_writable(Path::new(&game_root))?;
+    let installed = updater.installed_languages()?;
+    if !installed.unknown.is_empty() {
+        warn!("{}", t!(UnknownInstalledLanguages, installed.unknown.join(" ")));
+    }
+    info!("{}", t!(InstalledLanguage, installed.codes.join(" ")));
 
-    process_update_package(game_pkg.url.clone(), game_pkg.size, Path::new(&game_root))?;
+    // 有直接补丁时按补丁中的语音包大小显示，否则按完整安装包
+    let audio_pkgs = patches
+        .iter()
+        .find(|patch| from_version.is_some() && patch.version.parse().ok() == from_version)
+        .map_or(&game_package.main.major.audio_pkgs, |patch| &patch.audio_pkgs);
+    let languages = interactive::select_languages(audio_pkgs, &installed.codes, &cli.languages, unattended)?;
+    info!("{}", t!(ChosenLanguage, languages.join(" ")));
 
-    for audio_pkg in audio_pkgs.iter() {
-        process_update_package(audio_pkg.url.clone(), audio_pkg.size, Path::new(&game_root))?;
+    let path = updater.plan_upgrade(game_package, from_version, &languages)?;
+    info!(route = ?path.route, "{}", path.reason);
+    let summary = updater.summarize(game_package, &path)?;
+    if !interactive::confirm_plan(&path, &summary, cli.yes)? {
+        return Err(Error::Cancelled(t!(UpdateNotStarted).to_string()));
     }
 
-    println!("✅ 完成更新！");
+    // 每一步的更新包全部应用成功后才会记录该步的版本
+    updater.apply_path(&path, cli.sequential, stats)?;
+
+    check_update(updater, stats, Some(&game_package.main.major.res_list_url), cli.repair)?;
+
+    info!("{}", t!(UpdateDone));
 
     Ok(())
 }
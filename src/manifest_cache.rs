@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{IoContext, Result};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// 上一次成功获取的 API 清单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedManifest {
+    pub url: String,
+    pub fetched_at: DateTime<Utc>,
+    /// 用于条件请求的 `ETag`
+    pub etag: Option<String>,
+    /// 用于条件请求的 `Last-Modified`
+    pub last_modified: Option<String>,
+    /// 原始响应内容
+    pub body: String,
+}
+
+/// 读取缓存的清单，不存在或无法解析时返回 `None`
+pub fn load(cache_dir: &Path) -> Option<CachedManifest> {
+    let data = fs::read_to_string(cache_dir.join(MANIFEST_FILE)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+/// 保存清单，先写临时文件再重命名
+pub fn save(cache_dir: &Path, manifest: &CachedManifest) -> Result<()> {
+    fs::create_dir_all(cache_dir).with_path(cache_dir)?;
+
+    let path = cache_dir.join(MANIFEST_FILE);
+    let tmp_path = cache_dir.join(format!("{}.tmp", MANIFEST_FILE));
+    let data = serde_json::to_string_pretty(manifest).expect("manifest is serializable");
+    fs::write(&tmp_path, data).with_path(&tmp_path)?;
+    fs::rename(&tmp_path, &path).with_path(&path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_save_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(load(temp_dir.path()).is_none());
+
+        let manifest = CachedManifest {
+            url: "http://example.com/api".to_string(),
+            fetched_at: Utc::now(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            body: "{}".to_string(),
+        };
+        save(temp_dir.path(), &manifest).unwrap();
+
+        let loaded = load(temp_dir.path()).unwrap();
+        assert_eq!(loaded.url, manifest.url);
+        assert_eq!(loaded.etag, manifest.etag);
+        assert_eq!(loaded.fetched_at, manifest.fetched_at);
+    }
+}
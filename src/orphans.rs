@@ -0,0 +1,159 @@
+//! 清理不在任何 `pkg_version` 中的残留文件
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tracing::debug;
+
+use crate::error::{Error, Result};
+use crate::language::DATA_DIRS;
+use crate::pkg_version::{pkg_version_files, read_pkg_version};
+use crate::progress::{Event, Phase, ProgressSink};
+use crate::t;
+
+/// 始终保留的文件：截图、日志、用户配置与清单本身
+///
+/// 以 `*` 开头的规则匹配后缀，其余规则匹配该路径本身或其下的所有文件
+const PROTECTED: &[&str] = &[
+    "config.ini",
+    "*pkg_version",
+    "ScreenShot",
+    "*.log",
+    "*.dmp",
+];
+
+/// 每个数据目录下需要保留的子目录
+const PROTECTED_DATA_DIRS: &[&str] = &["Persistent", "webCaches", "SDKCaches"];
+
+/// 残留文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct Orphan {
+    /// 相对游戏目录的路径，使用 `/` 分隔
+    pub path: String,
+    pub size: u64,
+}
+
+fn matches(pattern: &str, path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    let pattern = pattern.trim_end_matches('/').to_ascii_lowercase();
+    match pattern.strip_prefix('*') {
+        Some(suffix) => path.ends_with(suffix),
+        None => path == pattern || path.starts_with(&format!("{}/", pattern)),
+    }
+}
+
+/// 是否在保留名单中，`extra` 为配置中追加的规则
+pub fn is_protected(path: &str, extra: &[String]) -> bool {
+    PROTECTED.iter().any(|pattern| matches(pattern, path))
+        || DATA_DIRS.iter().any(|data_dir| {
+            PROTECTED_DATA_DIRS
+                .iter()
+                .any(|dir| matches(&format!("{}/{}", data_dir, dir), path))
+        })
+        || extra.iter().any(|pattern| matches(pattern, path))
+}
+
+/// 列出游戏目录中既不在 `pkg_version` 中、也不在保留名单中的文件
+pub fn find_orphans(game_dir: &Path, extra_protected: &[String]) -> Result<Vec<Orphan>> {
+    // 没有游戏本体的清单时几乎所有文件都会被当作残留，只有语音包清单也不行
+    if !game_dir.join("pkg_version").is_file() {
+        return Err(Error::Plan(t!(OrphansNeedPkgVersion, game_dir.display())));
+    }
+    let files = pkg_version_files(game_dir)?;
+
+    let mut known = HashSet::new();
+    for file in files {
+        for entry in read_pkg_version(&file)? {
+            known.insert(entry.remote_name.to_ascii_lowercase());
+        }
+    }
+
+    let mut orphans = Vec::new();
+    for entry in walkdir::WalkDir::new(game_dir).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(relative_path) = entry.path().strip_prefix(game_dir) else {
+            continue;
+        };
+        let path = relative_path.to_string_lossy().replace('\\', "/");
+        if known.contains(&path.to_ascii_lowercase()) || is_protected(&path, extra_protected) {
+            continue;
+        }
+
+        let size = entry.metadata().map(|meta| meta.len()).unwrap_or(0);
+        orphans.push(Orphan { path, size });
+    }
+    orphans.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(orphans)
+}
+
+/// 删除残留文件，返回释放的字节数
+pub fn remove_orphans(game_dir: &Path, orphans: &[Orphan], progress: &dyn ProgressSink) -> Result<u64> {
+    let mut freed = 0;
+
+    progress.emit(Event::PhaseStarted { phase: Phase::Delete, total: orphans.len() as u64 });
+    for orphan in orphans {
+        let path = game_dir.join(&orphan.path);
+        debug!(path = %path.display(), "{}", t!(OrphanDeleted));
+        fs::remove_file(&path).map_err(|source| Error::Delete { path: path.clone(), source })?;
+        freed += orphan.size;
+        progress.emit(Event::FileDeleted { path: orphan.path.clone() });
+    }
+    progress.emit(Event::PhaseFinished { phase: Phase::Delete });
+
+    Ok(freed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_orphans_respects_pkg_version_and_allowlist() {
+        let temp_dir = TempDir::new().unwrap();
+        let game_dir = temp_dir.path();
+        let write = |path: &str| {
+            let path = game_dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"data").unwrap();
+        };
+
+        fs::write(
+            game_dir.join("pkg_version"),
+            "{\"remoteName\": \"GenshinImpact.exe\", \"md5\": \"aa\", \"fileSize\": 4}\n",
+        ).unwrap();
+        write("GenshinImpact.exe");
+        write("config.ini");
+        write("ScreenShot/20240101.png");
+        write("GenshinImpact_Data/Persistent/audio_lang_14");
+        write("GenshinImpact_Data/StreamingAssets/old.blk");
+        write("mods/keep.txt");
+        write("stale.dll");
+
+        let orphans = find_orphans(game_dir, &["mods".to_string()]).unwrap();
+        let paths: Vec<&str> = orphans.iter().map(|orphan| orphan.path.as_str()).collect();
+        assert_eq!(paths, vec!["GenshinImpact_Data/StreamingAssets/old.blk", "stale.dll"]);
+
+        let freed = remove_orphans(game_dir, &orphans, &crate::progress::NoopSink).unwrap();
+        assert_eq!(freed, 8);
+        assert!(!game_dir.join("stale.dll").exists());
+        assert!(game_dir.join("mods/keep.txt").exists());
+    }
+
+    #[test]
+    fn test_find_orphans_requires_pkg_version() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.txt"), b"a").unwrap();
+        assert!(find_orphans(temp_dir.path(), &[]).is_err());
+
+        // 只有语音包清单时同样拒绝
+        fs::write(
+            temp_dir.path().join("Audio_English(US)_pkg_version"),
+            "{\"remoteName\": \"GenshinImpact_Data/StreamingAssets/AudioAssets/English(US)/a.pck\", \"md5\": \"aa\", \"fileSize\": 1}\n",
+        ).unwrap();
+        assert!(matches!(find_orphans(temp_dir.path(), &[]), Err(Error::Plan(_))));
+    }
+}
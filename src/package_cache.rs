@@ -0,0 +1,213 @@
+//! 按 md5 寻址的更新包缓存，支持按大小与时间淘汰
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+use crate::error::{IoContext, Result};
+use crate::settings::CacheSettings;
+use crate::util::PART_SUFFIX;
+use crate::t;
+
+/// 更新包缓存在缓存目录下的位置
+pub const PACKAGES_DIR: &str = "packages";
+
+/// 记录下载地址的文件名后缀，与更新包放在一起
+const URL_SUFFIX: &str = ".url";
+
+/// 缓存中的一个更新包
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub md5: String,
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// 最后一次下载或使用的时间
+    pub used_at: SystemTime,
+    /// 下载地址，较早缓存的更新包没有记录
+    pub url: Option<String>,
+    /// 未下载完成的 `.part` 文件
+    pub partial: bool,
+}
+
+impl CacheEntry {
+    /// 下载完成并记录了下载地址，可以用于制定升级路线
+    pub fn is_usable(&self) -> bool {
+        !self.partial && self.url.is_some()
+    }
+}
+
+/// 更新包缓存，布局为 `<dir>/<md5>/<文件名>`
+#[derive(Debug, Clone)]
+pub struct PackageCache {
+    dir: PathBuf,
+}
+
+impl PackageCache {
+    pub fn new(cache_dir: impl AsRef<Path>) -> Self {
+        PackageCache { dir: cache_dir.as_ref().join(PACKAGES_DIR) }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// md5 为 `md5`、下载地址为 `url` 的更新包在缓存中的路径
+    pub fn path_for(&self, md5: &str, url: &str) -> PathBuf {
+        let name = url.split('/').next_back().unwrap_or(url);
+        self.dir.join(md5.to_ascii_lowercase()).join(name)
+    }
+
+    /// 记录更新包的下载地址，缓存校验失败时可以重新下载
+    pub fn record_url(&self, path: &Path, url: &str) -> Result<()> {
+        let url_path = sidecar(path, URL_SUFFIX);
+        fs::write(&url_path, url).with_path(&url_path)
+    }
+
+    /// 记录更新包被使用，推迟其按时间淘汰
+    pub fn touch(&self, path: &Path) -> Result<()> {
+        let file = File::options().write(true).open(path).with_path(path)?;
+        file.set_modified(SystemTime::now()).with_path(path)
+    }
+
+    /// 列出缓存中的更新包，最近使用的在前
+    pub fn list(&self) -> Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+        if !self.dir.is_dir() {
+            return Ok(entries);
+        }
+
+        for md5_dir in fs::read_dir(&self.dir).with_path(&self.dir)?.filter_map(|e| e.ok()) {
+            let md5_path = md5_dir.path();
+            if !md5_path.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(&md5_path).with_path(&md5_path)?.filter_map(|e| e.ok()) {
+                let path = file.path();
+                let meta = file.metadata().with_path(&path)?;
+                let name = file.file_name().to_string_lossy().to_string();
+                if !meta.is_file() || name.ends_with(URL_SUFFIX) {
+                    continue;
+                }
+                let (name, partial) = match name.strip_suffix(PART_SUFFIX) {
+                    Some(name) => (name.to_string(), true),
+                    None => (name, false),
+                };
+                entries.push(CacheEntry {
+                    md5: md5_dir.file_name().to_string_lossy().to_string(),
+                    size: meta.len(),
+                    used_at: meta.modified().with_path(&path)?,
+                    url: fs::read_to_string(sidecar(&md5_path.join(&name), URL_SUFFIX)).ok(),
+                    name,
+                    partial,
+                    path,
+                });
+            }
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.used_at));
+
+        Ok(entries)
+    }
+
+    /// 删除超过保留时间的更新包，再从最久未使用的开始删除直到总大小不超过上限
+    ///
+    /// 返回被删除的更新包
+    pub fn prune(&self, settings: &CacheSettings) -> Result<Vec<CacheEntry>> {
+        let now = SystemTime::now();
+        let max_age = Duration::from_secs(settings.max_age_days * 24 * 60 * 60);
+        let max_size = settings.max_size_mb * 1024 * 1024;
+
+        let mut kept = Vec::new();
+        let mut removed = Vec::new();
+        for entry in self.list()? {
+            let age = now.duration_since(entry.used_at).unwrap_or_default();
+            if settings.max_age_days > 0 && age > max_age {
+                removed.push(entry);
+            } else {
+                kept.push(entry);
+            }
+        }
+
+        // 列表按最近使用排序，超出上限的部分从末尾淘汰
+        let mut total = 0;
+        for entry in kept {
+            total += entry.size;
+            if settings.max_size_mb > 0 && total > max_size {
+                removed.push(entry);
+            }
+        }
+
+        for entry in &removed {
+            debug!(path = %entry.path.display(), "{}", t!(CacheEntryEvicted));
+            self.remove(entry)?;
+        }
+
+        Ok(removed)
+    }
+
+    /// 清空缓存
+    pub fn clear(&self) -> Result<Vec<CacheEntry>> {
+        let entries = self.list()?;
+        for entry in &entries {
+            self.remove(entry)?;
+        }
+        Ok(entries)
+    }
+
+    fn remove(&self, entry: &CacheEntry) -> Result<()> {
+        fs::remove_file(&entry.path).with_path(&entry.path)?;
+        // 完整的更新包和未完成的下载都不在了，下载地址也不再需要
+        let complete = entry.path.with_file_name(&entry.name);
+        if !complete.exists() {
+            let _ = fs::remove_file(sidecar(&complete, URL_SUFFIX));
+        }
+        if let Some(parent) = entry.path.parent() {
+            // 同一 md5 目录下没有其他文件时一并删除
+            let _ = fs::remove_dir(parent);
+        }
+        Ok(())
+    }
+}
+
+/// 与 `path` 放在一起、文件名加上 `suffix` 的文件
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_os_string();
+    sidecar.push(suffix);
+    PathBuf::from(sidecar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn add(cache: &PackageCache, md5: &str, size: usize, age_days: u64) -> PathBuf {
+        let path = cache.path_for(md5, &format!("http://example.com/{}.zip", md5));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, vec![0; size]).unwrap();
+        let used_at = SystemTime::now() - Duration::from_secs(age_days * 24 * 60 * 60);
+        File::options().write(true).open(&path).unwrap().set_modified(used_at).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_prune_by_age_and_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = PackageCache::new(temp_dir.path());
+
+        let old = add(&cache, "aa", 10, 40);
+        let recent = add(&cache, "bb", 600 * 1024, 1);
+        let newest = add(&cache, "cc", 600 * 1024, 0);
+        assert_eq!(cache.list().unwrap().len(), 3);
+
+        let settings = CacheSettings { max_size_mb: 1, max_age_days: 30 };
+        let removed = cache.prune(&settings).unwrap();
+
+        assert_eq!(removed.len(), 2);
+        assert!(!old.exists());
+        assert!(!old.parent().unwrap().exists());
+        assert!(!recent.exists());
+        assert!(newest.exists());
+    }
+}
@@ -1,7 +1,10 @@
 use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use crate::error::{Error, Result};
+use crate::t;
 
-mod u64_string {
-    use serde::{Deserialize, Deserializer};
+pub(crate) mod u64_string {
+    use serde::Deserializer;
     use std::fmt;
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<u64, D::Error>
@@ -14,7 +17,14 @@ mod u64_string {
             type Value = u64;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("a string representing a u64")
+                formatter.write_str("a u64 or a string representing a u64")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<u64, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(value)
             }
 
             fn visit_str<E>(self, value: &str) -> Result<u64, E>
@@ -25,20 +35,88 @@ mod u64_string {
             }
         }
 
-        deserializer.deserialize_str(U64Visitor)
+        deserializer.deserialize_any(U64Visitor)
+    }
+}
+
+/// 按字段路径报告格式错误的反序列化
+fn from_json<T: DeserializeOwned>(body: &str) -> Result<T> {
+    let deserializer = &mut serde_json::Deserializer::from_str(body);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| Error::Schema {
+        field: e.path().to_string(),
+        message: e.inner().to_string(),
+    })
+}
+
+/// 通用的 API 响应外层结构
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    retcode: i32,
+    #[serde(default)]
+    message: String,
+    data: Option<T>,
+}
+
+/// 解析 `{retcode, message, data}` 格式的 API 响应并取出 `data`
+pub fn parse_api<T: DeserializeOwned>(body: &str) -> Result<T> {
+    let envelope: Envelope<T> = from_json(body)?;
+    if envelope.retcode != 0 {
+        return Err(Error::ApiRetcode {
+            retcode: envelope.retcode,
+            message: envelope.message,
+        });
     }
+    envelope.data.ok_or_else(|| Error::Schema {
+        field: "data".to_string(),
+        message: t!(MissingData).to_string(),
+    })
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Response {
     pub retcode: i32,
+    #[serde(default)]
     pub message: String,
-    pub data: Data,
+    // 出错时 API 可能返回 `"data": null`
+    #[serde(default)]
+    pub data: Option<Data>,
+}
+
+impl Response {
+    /// 解析 API 响应，格式不符时报告出错的字段，retcode 非零时返回错误
+    pub fn parse(body: &str) -> Result<Response> {
+        let response: Response = from_json(body)?;
+
+        if response.retcode != 0 {
+            return Err(Error::ApiRetcode {
+                retcode: response.retcode,
+                message: response.message,
+            });
+        }
+
+        Ok(response)
+    }
+
+    /// 清单中的所有游戏
+    pub fn game_packages(&self) -> &[GamePackage] {
+        self.data.as_ref().map_or(&[], |data| &data.game_packages)
+    }
+
+    /// 清单中的第一个游戏包
+    pub fn game_package(&self) -> Result<&GamePackage> {
+        self.data
+            .as_ref()
+            .and_then(|data| data.game_packages.first())
+            .ok_or_else(|| Error::Schema {
+                field: "data.game_packages".to_string(),
+                message: t!(NoGamePackages).to_string(),
+            })
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Data {
-    #[serde(rename = "game_packages")]
+    #[serde(rename = "game_packages", default)]
     pub game_packages: Vec<GamePackage>,
 }
 
@@ -46,40 +124,43 @@ pub struct Data {
 pub struct GamePackage {
     pub game: Game,
     pub main: Main,
-    #[serde(rename = "pre_download")]
-    pub pre_download: PreDownload,
+    #[serde(rename = "pre_download", default)]
+    pub pre_download: Option<PreDownload>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Game {
     pub id: String,
+    #[serde(default)]
     pub biz: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Main {
     pub major: Major,
+    #[serde(default)]
     pub patches: Vec<Patch>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Major {
     pub version: String,
-    #[serde(rename = "game_pkgs")]
+    #[serde(rename = "game_pkgs", default)]
     pub game_pkgs: Vec<GamePkg>,
-    #[serde(rename = "audio_pkgs")]
+    #[serde(rename = "audio_pkgs", default)]
     pub audio_pkgs: Vec<AudioPkg>,
-    #[serde(rename = "res_list_url")]
+    #[serde(rename = "res_list_url", default)]
     pub res_list_url: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GamePkg {
     pub url: String,
+    #[serde(default)]
     pub md5: String,
     #[serde(deserialize_with = "u64_string::deserialize")]
     pub size: u64,
-    #[serde(rename = "decompressed_size", deserialize_with = "u64_string::deserialize")]
+    #[serde(rename = "decompressed_size", deserialize_with = "u64_string::deserialize", default)]
     pub decompressed_size: u64,
 }
 
@@ -87,27 +168,30 @@ pub struct GamePkg {
 pub struct AudioPkg {
     pub language: String,
     pub url: String,
+    #[serde(default)]
     pub md5: String,
     #[serde(deserialize_with = "u64_string::deserialize")]
     pub size: u64,
-    #[serde(rename = "decompressed_size", deserialize_with = "u64_string::deserialize")]
+    #[serde(rename = "decompressed_size", deserialize_with = "u64_string::deserialize", default)]
     pub decompressed_size: u64,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Patch {
     pub version: String,
-    #[serde(rename = "game_pkgs")]
+    #[serde(rename = "game_pkgs", default)]
     pub game_pkgs: Vec<GamePkg>,
-    #[serde(rename = "audio_pkgs")]
+    #[serde(rename = "audio_pkgs", default)]
     pub audio_pkgs: Vec<AudioPkg>,
-    #[serde(rename = "res_list_url")]
+    #[serde(rename = "res_list_url", default)]
     pub res_list_url: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct PreDownload {
+    #[serde(default)]
     pub major: Option<Major>,
+    #[serde(default)]
     pub patches: Vec<Patch>,
 }
 
@@ -175,6 +259,57 @@ mod tests {
 
         let response: Response = serde_json::from_str(sample_response).unwrap();
         assert_eq!(response.retcode, 0);
-        assert_eq!(response.data.game_packages[0].game.id, "gopR6Cufr3");
+        assert_eq!(response.game_package().unwrap().game.id, "gopR6Cufr3");
+    }
+
+    #[test]
+    fn test_optional_fields_missing() {
+        let sample_response = r#"
+        {
+            "retcode": 0,
+            "data": {
+                "game_packages": [
+                    {
+                        "game": {"id": "gopR6Cufr3"},
+                        "main": {
+                            "major": {"version": "5.1.0"},
+                            "patches": [
+                                {
+                                    "version": "5.0.0",
+                                    "game_pkgs": [{"url": "http://example.com/game", "size": 1024}]
+                                }
+                            ]
+                        }
+                    }
+                ]
+            }
+        }"#;
+
+        let response = Response::parse(sample_response).unwrap();
+        let game_package = response.game_package().unwrap();
+        assert!(game_package.pre_download.is_none());
+        assert_eq!(game_package.main.patches[0].res_list_url, "");
+        assert_eq!(game_package.main.patches[0].game_pkgs[0].size, 1024);
+    }
+
+    #[test]
+    fn test_retcode_error() {
+        let body = r#"{"retcode": -502, "message": "invalid game id", "data": null}"#;
+        match Response::parse(body) {
+            Err(Error::ApiRetcode { retcode, message }) => {
+                assert_eq!(retcode, -502);
+                assert_eq!(message, "invalid game id");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_schema_error_reports_field() {
+        let body = r#"{"retcode": 0, "data": {"game_packages": [{"game": {"id": "x"}, "main": {"major": {"version": 5}}}]}}"#;
+        match Response::parse(body) {
+            Err(Error::Schema { field, .. }) => assert_eq!(field, "data.game_packages[0].main.major.version"),
+            other => panic!("unexpected result: {:?}", other),
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,313 @@
+//! 更新期间临时放宽权限：只处理计划中的文件及其所在目录，结束后恢复原始权限；
+//! 以 root 运行时把写入的文件交还给游戏目录的属主
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, Permissions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{debug, warn};
+
+use crate::error::{IoContext, Result};
+use crate::t;
+
+/// 文件属主
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Owner {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Owner {
+    /// 读取 `path` 的属主
+    #[cfg(unix)]
+    pub fn of(path: &Path) -> io::Result<Owner> {
+        use std::os::unix::fs::MetadataExt;
+
+        let meta = fs::metadata(path)?;
+        Ok(Owner { uid: meta.uid(), gid: meta.gid() })
+    }
+
+    #[cfg(not(unix))]
+    pub fn of(_path: &Path) -> io::Result<Owner> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "file ownership is only supported on unix"))
+    }
+
+    #[cfg(unix)]
+    fn chown(&self, path: &Path) -> io::Result<()> {
+        std::os::unix::fs::lchown(path, Some(self.uid), Some(self.gid))
+    }
+
+    #[cfg(not(unix))]
+    fn chown(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn lookup_user(name: &str) -> Option<Owner> {
+    let name = std::ffi::CString::new(name).ok()?;
+    // SAFETY: getpwnam 返回的结构在下一次调用前有效，这里立即复制所需字段
+    let passwd = unsafe { libc::getpwnam(name.as_ptr()) };
+    (!passwd.is_null()).then(|| unsafe { Owner { uid: (*passwd).pw_uid, gid: (*passwd).pw_gid } })
+}
+
+#[cfg(unix)]
+fn lookup_uid(uid: u32) -> Option<Owner> {
+    // SAFETY: 同 getpwnam
+    let passwd = unsafe { libc::getpwuid(uid) };
+    (!passwd.is_null()).then(|| unsafe { Owner { uid: (*passwd).pw_uid, gid: (*passwd).pw_gid } })
+}
+
+#[cfg(unix)]
+fn lookup_group(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    // SAFETY: 同 getpwnam
+    let group = unsafe { libc::getgrnam(name.as_ptr()) };
+    (!group.is_null()).then(|| unsafe { (*group).gr_gid })
+}
+
+#[cfg(not(unix))]
+fn lookup_user(_name: &str) -> Option<Owner> {
+    None
+}
+
+#[cfg(not(unix))]
+fn lookup_uid(_uid: u32) -> Option<Owner> {
+    None
+}
+
+#[cfg(not(unix))]
+fn lookup_group(_name: &str) -> Option<u32> {
+    None
+}
+
+/// 解析 `用户[:组]`，用户与组可以是名称或数字；省略组时使用用户的主组
+impl FromStr for Owner {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let (user, group) = match value.split_once(':') {
+            Some((user, group)) => (user, Some(group)),
+            None => (value, None),
+        };
+
+        let mut owner = match user.parse::<u32>() {
+            Ok(uid) => lookup_uid(uid).unwrap_or(Owner { uid, gid: uid }),
+            Err(_) => lookup_user(user).ok_or_else(|| t!(UnknownUser, user))?,
+        };
+        if let Some(group) = group {
+            owner.gid = match group.parse::<u32>() {
+                Ok(gid) => gid,
+                Err(_) => lookup_group(group).ok_or_else(|| t!(UnknownGroup, group))?,
+            };
+        }
+
+        Ok(owner)
+    }
+}
+
+/// 是否以 root 运行
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    // SAFETY: geteuid 没有前置条件
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
+
+/// 记录被修改过权限的文件与目录，`restore` 或析构时恢复
+///
+/// 被替换的文件恢复为旧文件的权限，可执行位随之保留；设置了属主时，
+/// 写入的文件与新建的目录在恢复权限前交给该属主
+#[derive(Debug, Default)]
+pub struct ModeGuard {
+    files: HashMap<PathBuf, Permissions>,
+    dirs: HashMap<PathBuf, Permissions>,
+    owner: Option<Owner>,
+    written: HashSet<PathBuf>,
+}
+
+#[cfg(unix)]
+fn relaxed(permissions: &Permissions, is_dir: bool) -> Option<Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+
+    // 文件需要读写，目录还需要进入
+    let wanted = if is_dir { 0o700 } else { 0o600 };
+    let mode = permissions.mode();
+    (mode & wanted != wanted).then(|| Permissions::from_mode(mode | wanted))
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::permissions_set_readonly_false)]
+fn relaxed(permissions: &Permissions, _is_dir: bool) -> Option<Permissions> {
+    if !permissions.readonly() {
+        return None;
+    }
+    let mut permissions = permissions.clone();
+    permissions.set_readonly(false);
+    Some(permissions)
+}
+
+impl ModeGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 写入的文件交给 `owner`，为空时不修改属主
+    pub fn with_owner(mut self, owner: Option<Owner>) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    /// 准备读写、替换或删除 `path`：记录并放宽该文件与最近一个已存在的上级目录的权限
+    pub fn prepare(&mut self, path: &Path) -> io::Result<()> {
+        if self.owner.is_some() {
+            // 文件本身以及之后才会创建的上级目录
+            for created in path.ancestors().take_while(|dir| !dir.is_dir()) {
+                self.written.insert(created.to_path_buf());
+            }
+        }
+
+        if let Ok(meta) = fs::symlink_metadata(path) {
+            if meta.is_file() && !self.files.contains_key(path) {
+                let original = meta.permissions();
+                if let Some(permissions) = relaxed(&original, false) {
+                    fs::set_permissions(path, permissions)?;
+                }
+                self.files.insert(path.to_path_buf(), original);
+            }
+        }
+
+        let Some(dir) = path.ancestors().skip(1).find(|dir| dir.is_dir()) else {
+            return Ok(());
+        };
+        if !self.dirs.contains_key(dir) {
+            let original = fs::metadata(dir)?.permissions();
+            if let Some(permissions) = relaxed(&original, true) {
+                debug!(dir = %dir.display(), "{}", t!(DirModeRelaxed));
+                fs::set_permissions(dir, permissions)?;
+            }
+            self.dirs.insert(dir.to_path_buf(), original);
+        }
+
+        Ok(())
+    }
+
+    /// 记录调用方新建的文件或目录，恢复时一并交给属主
+    pub fn created(&mut self, path: &Path) {
+        if self.owner.is_some() {
+            self.written.insert(path.to_path_buf());
+        }
+    }
+
+    /// 修改写入文件的属主并恢复记录的权限；已被删除的文件跳过，目录从深到浅恢复
+    pub fn restore(&mut self) -> Result<()> {
+        if let Some(owner) = self.owner {
+            for path in self.written.drain() {
+                if fs::symlink_metadata(&path).is_ok() {
+                    debug!(path = %path.display(), uid = owner.uid, gid = owner.gid, "{}", t!(OwnerChanged));
+                    owner.chown(&path).with_path(&path)?;
+                }
+            }
+        }
+
+        for (path, permissions) in self.files.drain() {
+            if path.is_file() {
+                fs::set_permissions(&path, permissions).with_path(&path)?;
+            }
+        }
+
+        let mut dirs: Vec<(PathBuf, Permissions)> = self.dirs.drain().collect();
+        dirs.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.components().count()));
+        for (dir, permissions) in dirs {
+            if dir.is_dir() {
+                fs::set_permissions(&dir, permissions).with_path(&dir)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for ModeGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.restore() {
+            warn!(error = %e, "{}", t!(RestoreModesFailed));
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn test_restore_keeps_modes_of_replaced_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("game");
+        fs::create_dir(&dir).unwrap();
+        let exe = dir.join("hpatchz");
+        let data = dir.join("data.blk");
+        let untouched = dir.join("other.blk");
+        for path in [&exe, &data, &untouched] {
+            fs::write(path, b"old").unwrap();
+        }
+        fs::set_permissions(&exe, Permissions::from_mode(0o555)).unwrap();
+        fs::set_permissions(&data, Permissions::from_mode(0o444)).unwrap();
+        fs::set_permissions(&untouched, Permissions::from_mode(0o444)).unwrap();
+        fs::set_permissions(&dir, Permissions::from_mode(0o555)).unwrap();
+
+        let mut guard = ModeGuard::new();
+        guard.prepare(&exe).unwrap();
+        guard.prepare(&data).unwrap();
+        assert_eq!(mode(&dir), 0o755);
+        assert_eq!(mode(&data), 0o644);
+
+        // 替换为新文件后恢复旧文件的权限
+        let staged = temp_dir.path().join("staged");
+        fs::write(&staged, b"new").unwrap();
+        fs::set_permissions(&staged, Permissions::from_mode(0o644)).unwrap();
+        fs::rename(&staged, &exe).unwrap();
+        guard.restore().unwrap();
+
+        assert_eq!(mode(&exe), 0o555);
+        assert_eq!(mode(&data), 0o444);
+        assert_eq!(mode(&untouched), 0o444);
+        assert_eq!(mode(&dir), 0o555);
+
+        fs::set_permissions(&dir, Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_owner_parse_and_chown_new_files() {
+        assert_eq!("0".parse::<Owner>().unwrap(), Owner { uid: 0, gid: 0 });
+        assert_eq!("1000:100".parse::<Owner>().unwrap(), Owner { uid: 1000, gid: 100 });
+        assert_eq!("root:0".parse::<Owner>().unwrap(), Owner { uid: 0, gid: 0 });
+        assert!("no-such-user-here".parse::<Owner>().is_err());
+
+        let temp_dir = TempDir::new().unwrap();
+        let owner = Owner::of(temp_dir.path()).unwrap();
+        let path = temp_dir.path().join("new/dir/file.blk");
+
+        let mut guard = ModeGuard::new().with_owner(Some(owner));
+        guard.prepare(&path).unwrap();
+        assert!(guard.written.contains(&temp_dir.path().join("new")));
+        assert!(guard.written.contains(&path));
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"new").unwrap();
+        guard.restore().unwrap();
+        assert_eq!(Owner::of(&path).unwrap(), owner);
+        assert!(guard.written.is_empty());
+    }
+}
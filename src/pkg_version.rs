@@ -0,0 +1,95 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use crate::error::{IoContext, Result};
+use crate::t;
+use serde::Deserialize;
+use tracing::warn;
+
+/// `pkg_version` 文件中的一条记录
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct PkgEntry {
+    #[serde(rename = "remoteName")]
+    pub remote_name: String,
+    pub md5: String,
+    #[serde(rename = "fileSize")]
+    pub file_size: u64,
+}
+
+/// 读取逐行 JSON 格式的 `pkg_version` 文件，无法解析的行会被跳过
+pub fn read_pkg_version(path: &Path) -> Result<Vec<PkgEntry>> {
+    let reader = BufReader::new(File::open(path).with_path(path)?);
+    let mut entries = Vec::new();
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line.with_path(path)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<PkgEntry>(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!(file = %path.display(), line = idx + 1, error = %e, "{}", t!(LineParseFailed, idx + 1)),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// 游戏目录下的所有 `pkg_version` 文件（游戏本体与各语音包）
+pub fn pkg_version_files(game_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    let main = game_dir.join("pkg_version");
+    if main.is_file() {
+        files.push(main);
+    }
+
+    if game_dir.is_dir() {
+        let mut audio: Vec<PathBuf> = fs::read_dir(game_dir).with_path(game_dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("Audio_") && n.ends_with("_pkg_version"))
+            })
+            .collect();
+        audio.sort();
+        files.extend(audio);
+    }
+
+    Ok(files)
+}
+
+/// 读取游戏目录下所有 `pkg_version` 记录
+pub fn read_all_pkg_versions(game_dir: &Path) -> Result<Vec<PkgEntry>> {
+    let mut entries = Vec::new();
+    for file in pkg_version_files(game_dir)? {
+        entries.extend(read_pkg_version(&file)?);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_all_pkg_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("pkg_version"),
+            "{\"remoteName\": \"GenshinImpact.exe\", \"md5\": \"aa\", \"fileSize\": 3}\r\nbroken\r\n",
+        ).unwrap();
+        fs::write(
+            temp_dir.path().join("Audio_Japanese_pkg_version"),
+            "{\"remoteName\": \"GenshinImpact_Data/StreamingAssets/AudioAssets/Japanese/a.pck\", \"md5\": \"bb\", \"fileSize\": 5}\n",
+        ).unwrap();
+
+        let entries = read_all_pkg_versions(temp_dir.path()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].remote_name, "GenshinImpact.exe");
+        assert_eq!(entries[1].file_size, 5);
+    }
+}
@@ -0,0 +1,243 @@
+use std::io::{self, Write};
+use std::sync::Mutex;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde::Serialize;
+
+use crate::t;
+
+/// JSON 模式下下载进度事件的最小间隔（字节）
+const JSON_BYTES_STEP: u64 = 1024 * 1024;
+
+/// 更新流程的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Download,
+    Extract,
+    Patch,
+    Delete,
+    Copy,
+    /// Sophon 分块组装
+    Assemble,
+    Cleanup,
+    /// 更新后校验
+    Verify,
+    /// 重新下载损坏的文件
+    Repair,
+}
+
+/// 更新过程中产生的进度事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    PhaseStarted { phase: Phase, total: u64 },
+    PhaseFinished { phase: Phase },
+    BytesDownloaded { url: String, downloaded: u64, total: u64 },
+    FileExtracted { path: String },
+    FilePatched { path: String },
+    FileDeleted { path: String },
+    FileCopied { path: String },
+    FileAssembled { path: String },
+    FileVerified { path: String, ok: bool },
+    FileRepaired { path: String },
+    Error { message: String },
+    Summary {
+        success: bool,
+        packages: usize,
+        patched: u64,
+        deleted: u64,
+        copied: u64,
+        skipped: u64,
+        elapsed_secs: f64,
+    },
+}
+
+/// 接收更新进度事件的接口，库的调用方可自行实现
+pub trait ProgressSink: Send + Sync {
+    fn emit(&self, event: Event);
+
+    /// 在不打乱进度显示的情况下执行输出
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        f()
+    }
+}
+
+/// 忽略所有事件
+pub struct NoopSink;
+
+impl ProgressSink for NoopSink {
+    fn emit(&self, _event: Event) {}
+}
+
+/// 在标准输出逐行输出 JSON 事件
+#[derive(Default)]
+pub struct JsonSink {
+    last_bytes: Mutex<u64>,
+}
+
+impl JsonSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ProgressSink for JsonSink {
+    fn emit(&self, event: Event) {
+        // 下载进度按步长节流，避免刷屏
+        if let Event::BytesDownloaded { downloaded, total, .. } = &event {
+            let mut last_bytes = self.last_bytes.lock().unwrap();
+            if *downloaded < *last_bytes {
+                *last_bytes = 0;
+            }
+            if *downloaded - *last_bytes < JSON_BYTES_STEP && downloaded < total {
+                return;
+            }
+            *last_bytes = *downloaded;
+        }
+
+        let line = serde_json::to_string(&event).expect("event is serializable");
+        let mut stdout = io::stdout().lock();
+        let _ = writeln!(stdout, "{}", line);
+        let _ = stdout.flush();
+    }
+}
+
+/// 终端进度条，每个阶段一条
+///
+/// 下载与其他阶段各占一行，边下载边应用时同时显示
+#[derive(Default)]
+pub struct BarSink {
+    multi: MultiProgress,
+    download: Mutex<Option<ProgressBar>>,
+    work: Mutex<Option<ProgressBar>>,
+}
+
+impl BarSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn template(phase: Phase) -> &'static str {
+        match phase {
+            Phase::Download => "[{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})",
+            Phase::Extract => t!(TplExtract),
+            Phase::Patch => "{prefix:.green} {wide_bar} {pos}/{len} {msg}",
+            Phase::Delete => t!(TplDelete),
+            Phase::Copy => t!(TplCopy),
+            Phase::Assemble => t!(TplAssemble),
+            Phase::Cleanup => t!(TplCleanup),
+            Phase::Verify => t!(TplVerify),
+            Phase::Repair => t!(TplRepair),
+        }
+    }
+
+    fn slot(&self, phase: Phase) -> &Mutex<Option<ProgressBar>> {
+        match phase {
+            Phase::Download => &self.download,
+            _ => &self.work,
+        }
+    }
+
+    fn finish_message(phase: Phase) -> &'static str {
+        match phase {
+            Phase::Download => t!(DownloadDone),
+            Phase::Extract => t!(DoneExtract),
+            Phase::Patch => t!(DonePatch),
+            Phase::Delete => t!(DoneDelete),
+            Phase::Copy => t!(DoneCopy),
+            Phase::Assemble => t!(DoneAssemble),
+            Phase::Cleanup => t!(DoneCleanup),
+            Phase::Verify => t!(DoneVerify),
+            Phase::Repair => t!(DoneRepair),
+        }
+    }
+}
+
+impl ProgressSink for BarSink {
+    fn emit(&self, event: Event) {
+        match event {
+            Event::PhaseStarted { phase, total } => {
+                let pb = self.multi.add(ProgressBar::new(total));
+                pb.set_style(
+                    ProgressStyle::with_template(Self::template(phase))
+                        .expect("valid template")
+                        .progress_chars("=>-"),
+                );
+                if phase == Phase::Patch {
+                    pb.set_prefix(t!(PatchPrefix));
+                }
+                *self.slot(phase).lock().unwrap() = Some(pb);
+            }
+            Event::PhaseFinished { phase } => {
+                if let Some(pb) = self.slot(phase).lock().unwrap().take() {
+                    pb.finish_with_message(Self::finish_message(phase));
+                }
+            }
+            Event::BytesDownloaded { downloaded, total, .. } => {
+                if let Some(pb) = self.download.lock().unwrap().as_ref() {
+                    pb.set_length(total);
+                    pb.set_position(downloaded);
+                }
+            }
+            Event::FileExtracted { .. }
+            | Event::FilePatched { .. }
+            | Event::FileDeleted { .. }
+            | Event::FileCopied { .. }
+            | Event::FileAssembled { .. }
+            | Event::FileVerified { .. }
+            | Event::FileRepaired { .. } => {
+                if let Some(pb) = self.work.lock().unwrap().as_ref() {
+                    pb.inc(1);
+                }
+            }
+            // 错误与汇总已经通过日志输出
+            Event::Error { .. } | Event::Summary { .. } => {}
+        }
+    }
+
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        self.multi.suspend(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_sink_default_suspend() {
+        struct Collect(Mutex<Vec<Event>>);
+        impl ProgressSink for Collect {
+            fn emit(&self, event: Event) {
+                self.0.lock().unwrap().push(event);
+            }
+        }
+
+        let sink = Collect(Mutex::new(Vec::new()));
+        sink.emit(Event::FilePatched { path: "a".to_string() });
+        let mut called = false;
+        sink.suspend(&mut || called = true);
+
+        assert!(called);
+        assert_eq!(sink.0.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = Event::BytesDownloaded {
+            url: "http://example.com/a.zip".to_string(),
+            downloaded: 10,
+            total: 20,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"bytes_downloaded","url":"http://example.com/a.zip","downloaded":10,"total":20}"#
+        );
+
+        let event = Event::PhaseStarted { phase: Phase::Patch, total: 3 };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"phase_started","phase":"patch","total":3}"#
+        );
+    }
+}
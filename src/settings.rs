@@ -0,0 +1,197 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::i18n::Locale;
+use crate::t;
+
+/// 默认配置文件
+pub const SETTINGS_FILE: &str = "updater.toml";
+
+/// 更新器配置（`updater.toml`）
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub download: DownloadSettings,
+    pub clean: CleanSettings,
+    pub cache: CacheSettings,
+    pub apply: ApplySettings,
+    pub ui: UiSettings,
+}
+
+/// URL 前缀替换规则
+#[derive(Debug, Clone, Deserialize)]
+pub struct RewriteRule {
+    pub from: String,
+    pub to: String,
+}
+
+/// 下载相关配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DownloadSettings {
+    /// 按顺序匹配的前缀替换规则，命中的第一条生效
+    pub rewrite: Vec<RewriteRule>,
+    /// 按顺序尝试的镜像根地址，替换原始地址的协议与主机部分
+    pub mirrors: Vec<String>,
+    /// 每个地址的最大尝试次数
+    pub retries: u8,
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        DownloadSettings {
+            rewrite: Vec::new(),
+            mirrors: Vec::new(),
+            retries: 5,
+        }
+    }
+}
+
+impl DownloadSettings {
+    /// 按尝试顺序列出下载地址：替换规则、各镜像，最后是原始地址
+    pub fn candidate_urls(&self, url: &str) -> Vec<String> {
+        let mut urls = Vec::new();
+
+        if let Some(rule) = self.rewrite.iter().find(|rule| url.starts_with(&rule.from)) {
+            urls.push(format!("{}{}", rule.to, &url[rule.from.len()..]));
+        }
+
+        if let Some(path) = url_path(url) {
+            for mirror in &self.mirrors {
+                urls.push(format!("{}/{}", mirror.trim_end_matches('/'), path));
+            }
+        }
+
+        urls.push(url.to_string());
+        urls.dedup();
+        urls
+    }
+}
+
+/// 残留文件清理配置
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CleanSettings {
+    /// 额外保留的路径：以 `*` 开头匹配后缀，否则匹配该路径及其下的文件
+    pub protect: Vec<String>,
+}
+
+/// 更新包缓存配置，0 表示不限制
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheSettings {
+    /// 缓存总大小上限（MiB）
+    pub max_size_mb: u64,
+    /// 未使用超过该天数的更新包会被淘汰
+    pub max_age_days: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        CacheSettings {
+            max_size_mb: 40 * 1024,
+            max_age_days: 30,
+        }
+    }
+}
+
+/// 界面相关配置
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiSettings {
+    /// 界面语言（`en` 或 `zh-CN`），未设置时按环境变量检测
+    pub locale: Option<Locale>,
+}
+
+/// 文件放置失败时的处理方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyErrorPolicy {
+    /// 立即中止更新
+    #[default]
+    #[value(help = t!(PolicyAbort))]
+    Abort,
+    /// 跳过该文件，结束时列出并交给校验修复
+    #[value(help = t!(PolicySkip))]
+    Skip,
+    /// 重试若干次，仍失败则中止
+    #[value(help = t!(PolicyRetry))]
+    Retry,
+}
+
+/// 应用更新包相关配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApplySettings {
+    /// 暂存区所在目录，默认为游戏目录；更新器只使用其中的 `_updater_staging` 子目录，
+    /// 放在其他文件系统时无法直接重命名
+    pub staging_dir: Option<PathBuf>,
+    pub on_copy_error: CopyErrorPolicy,
+    /// `retry` 策略下每个文件的重试次数
+    pub copy_retries: u8,
+}
+
+impl Default for ApplySettings {
+    fn default() -> Self {
+        ApplySettings {
+            staging_dir: None,
+            on_copy_error: CopyErrorPolicy::default(),
+            copy_retries: 3,
+        }
+    }
+}
+
+/// 去掉协议与主机后的路径（不含开头的 `/`）
+fn url_path(url: &str) -> Option<&str> {
+    let rest = url.split_once("://")?.1;
+    Some(rest.split_once('/').map_or("", |(_, path)| path))
+}
+
+/// 读取配置；未指定路径时尝试默认文件，不存在则使用默认配置
+pub fn load(path: Option<&Path>) -> Result<Settings> {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new(SETTINGS_FILE).is_file() => Path::new(SETTINGS_FILE),
+        None => return Ok(Settings::default()),
+    };
+
+    let config_err = |message: String| Error::Config { path: path.to_path_buf(), message };
+    let data = fs::read_to_string(path).map_err(|e| config_err(e.to_string()))?;
+    toml::from_str(&data).map_err(|e| config_err(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidate_urls() {
+        let settings: Settings = toml::from_str(r#"
+            [download]
+            mirrors = ["http://mirror-a.lan/genshin/", "http://mirror-b.lan"]
+
+            [[download.rewrite]]
+            from = "https://autopatchhk.yuanshen.com/"
+            to = "http://cache.lan/hk/"
+        "#).unwrap();
+
+        let url = "https://autopatchhk.yuanshen.com/client_app/update/game_5.0.0_5.1.0_hdiff.zip";
+        assert_eq!(
+            settings.download.candidate_urls(url),
+            vec![
+                "http://cache.lan/hk/client_app/update/game_5.0.0_5.1.0_hdiff.zip",
+                "http://mirror-a.lan/genshin/client_app/update/game_5.0.0_5.1.0_hdiff.zip",
+                "http://mirror-b.lan/client_app/update/game_5.0.0_5.1.0_hdiff.zip",
+                url,
+            ]
+        );
+        assert_eq!(settings.download.retries, 5);
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        assert!(toml::from_str::<Settings>("[download]\nmirror = []\n").is_err());
+    }
+}
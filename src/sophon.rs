@@ -0,0 +1,619 @@
+//! Sophon 分块下载协议：protobuf 清单 + zstd 压缩、按内容寻址的分块
+
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use prost::Message;
+use reqwest::blocking::Client;
+use reqwest::header::USER_AGENT;
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+
+use crate::error::{Error, IoContext, Result};
+use crate::parser::{parse_api, u64_string};
+use crate::permissions::{ModeGuard, Owner};
+use crate::progress::{Event, NoopSink, Phase, ProgressSink};
+use crate::settings::DownloadSettings;
+use crate::util::{download_with_resume, file_md5, UpdateStats};
+use crate::t;
+
+pub const BRANCHES_API_URL: &str = "https://sg-hyp-api.hoyoverse.com/hyp/hyp-connect/api/getGameBranches?game_ids[]=gopR6Cufr3&launcher_id=VYTpXlbWo8";
+pub const BUILD_API_URL: &str = "https://sg-public-api.hoyoverse.com/downloader/sophon_chunk/api/getBuild";
+
+/// 清单中表示目录的标志位
+const DIRECTORY_FLAG: i32 = 64;
+/// 组装中的临时文件后缀
+const TMP_SUFFIX: &str = ".sophon_tmp";
+
+/// protobuf 清单
+#[derive(Clone, PartialEq, Message)]
+pub struct SophonManifest {
+    #[prost(message, repeated, tag = "1")]
+    pub files: Vec<SophonFile>,
+}
+
+/// 清单中的文件
+#[derive(Clone, PartialEq, Message)]
+pub struct SophonFile {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(message, repeated, tag = "2")]
+    pub chunks: Vec<SophonChunk>,
+    #[prost(int32, tag = "3")]
+    pub flags: i32,
+    #[prost(int64, tag = "4")]
+    pub size: i64,
+    #[prost(string, tag = "5")]
+    pub md5: String,
+}
+
+fn invalid(name: &str, message: String) -> Error {
+    Error::Manifest { name: name.to_string(), message }
+}
+
+/// 清单中的大小与偏移不能为负
+fn checked_u64(value: i64, field: &str, name: &str) -> Result<u64> {
+    u64::try_from(value).map_err(|_| invalid(name, t!(ManifestInvalidValue, field, value)))
+}
+
+impl SophonFile {
+    pub fn is_dir(&self) -> bool {
+        self.flags & DIRECTORY_FLAG != 0
+    }
+
+    /// 游戏目录中的路径；拒绝绝对路径与 `..`，避免写到游戏目录之外
+    pub fn local_path(&self, game_dir: &Path) -> Result<PathBuf> {
+        let path = Path::new(&self.name);
+        let escapes = path
+            .components()
+            .any(|component| matches!(component, Component::ParentDir | Component::RootDir | Component::Prefix(_)));
+        if self.name.is_empty() || escapes {
+            return Err(invalid(&self.name, t!(ManifestPathEscapes).to_string()));
+        }
+        Ok(game_dir.join(path))
+    }
+
+    pub fn file_size(&self) -> Result<u64> {
+        checked_u64(self.size, "size", &self.name)
+    }
+}
+
+/// 文件的一个分块
+#[derive(Clone, PartialEq, Message)]
+pub struct SophonChunk {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub decompressed_md5: String,
+    #[prost(int64, tag = "3")]
+    pub offset: i64,
+    #[prost(int64, tag = "4")]
+    pub compressed_size: i64,
+    #[prost(int64, tag = "5")]
+    pub decompressed_size: i64,
+    #[prost(uint64, tag = "6")]
+    pub compressed_xxh: u64,
+    #[prost(string, tag = "7")]
+    pub compressed_md5: String,
+}
+
+impl SophonChunk {
+    /// 分块文件名；只接受单个普通文件名，避免读写分块目录之外的文件
+    fn file_name(&self) -> Result<&OsStr> {
+        let mut components = Path::new(&self.name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) => Ok(name),
+            _ => Err(invalid(&self.name, t!(ManifestChunkName).to_string())),
+        }
+    }
+
+    /// 分块在 `chunk_dir` 中的路径
+    pub fn local_path(&self, chunk_dir: &Path) -> Result<PathBuf> {
+        Ok(chunk_dir.join(self.file_name()?))
+    }
+
+    pub fn start(&self) -> Result<u64> {
+        checked_u64(self.offset, "offset", &self.name)
+    }
+
+    pub fn compressed_len(&self) -> Result<u64> {
+        checked_u64(self.compressed_size, "compressed_size", &self.name)
+    }
+
+    pub fn decompressed_len(&self) -> Result<u64> {
+        checked_u64(self.decompressed_size, "decompressed_size", &self.name)
+    }
+
+    /// 分块在文件中的结束位置
+    fn end(&self) -> Result<u64> {
+        self.start()?
+            .checked_add(self.decompressed_len()?)
+            .ok_or_else(|| invalid(&self.name, t!(ManifestChunkOverflow).to_string()))
+    }
+}
+
+/// `getGameBranches` 的响应数据
+#[derive(Debug, Deserialize)]
+pub struct Branches {
+    #[serde(default)]
+    pub game_branches: Vec<GameBranch>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GameBranch {
+    pub main: BranchInfo,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BranchInfo {
+    pub package_id: String,
+    pub branch: String,
+    pub password: String,
+    pub tag: String,
+}
+
+/// `getBuild` 的响应数据
+#[derive(Debug, Deserialize)]
+pub struct Build {
+    pub tag: String,
+    #[serde(default)]
+    pub manifests: Vec<BuildManifest>,
+}
+
+/// 一个分类（游戏本体或某个语音包）的清单信息
+#[derive(Debug, Deserialize)]
+pub struct BuildManifest {
+    /// `game` 或语言代码
+    pub matching_field: String,
+    pub manifest: ManifestInfo,
+    pub chunk_download: DownloadInfo,
+    pub manifest_download: DownloadInfo,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ManifestInfo {
+    pub id: String,
+    #[serde(default)]
+    pub checksum: String,
+    #[serde(deserialize_with = "u64_string::deserialize", default)]
+    pub uncompressed_size: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DownloadInfo {
+    /// 1 表示 zstd 压缩
+    #[serde(default)]
+    pub compression: i32,
+    pub url_prefix: String,
+    #[serde(default)]
+    pub url_suffix: String,
+}
+
+impl DownloadInfo {
+    pub fn url(&self, name: &str) -> String {
+        format!("{}{}/{}", self.url_prefix.trim_end_matches('/'), self.url_suffix, name)
+    }
+}
+
+/// 单个文件的更新计划
+#[derive(Debug, Clone)]
+pub struct FilePlan {
+    pub file: SophonFile,
+    /// 需要下载的分块
+    pub missing: Vec<SophonChunk>,
+    /// 可以从本地旧文件复用的分块
+    pub reused: Vec<SophonChunk>,
+}
+
+/// 一个清单的更新计划
+#[derive(Debug, Default)]
+pub struct SophonPlan {
+    pub files: Vec<FilePlan>,
+    pub unchanged: usize,
+}
+
+impl SophonPlan {
+    /// 需要下载的压缩字节数，相同分块只计一次
+    pub fn download_bytes(&self) -> Result<u64> {
+        let mut seen = HashSet::new();
+        self.files
+            .iter()
+            .flat_map(|plan| &plan.missing)
+            .filter(|chunk| seen.insert(chunk.name.as_str()))
+            .map(|chunk| chunk.compressed_len())
+            .sum()
+    }
+}
+
+fn fetch_bytes(url: &str) -> Result<Vec<u8>> {
+    let download_err = |e: reqwest::Error| Error::Download { url: url.to_string(), message: e.to_string() };
+    debug!(url, "{}", t!(RequestSent));
+    let bytes = Client::new()
+        .get(url)
+        .header(USER_AGENT, "genshin-updater")
+        .send()
+        .and_then(|res| res.error_for_status())
+        .and_then(|res| res.bytes())
+        .map_err(download_err)?;
+    Ok(bytes.to_vec())
+}
+
+fn fetch_api<T: serde::de::DeserializeOwned>(url: &str) -> Result<T> {
+    info!(url, "{}", t!(FetchingSophon));
+    let body = fetch_bytes(url).map_err(|e| match e {
+        Error::Download { url, message } => Error::Api { url, message },
+        other => other,
+    })?;
+    parse_api(&String::from_utf8_lossy(&body))
+}
+
+/// 获取主分支信息
+pub fn fetch_branch(branches_url: &str) -> Result<BranchInfo> {
+    let branches: Branches = fetch_api(branches_url)?;
+    branches.game_branches
+        .into_iter()
+        .next()
+        .map(|branch| branch.main)
+        .ok_or_else(|| Error::Schema {
+            field: "data.game_branches".to_string(),
+            message: t!(NoGameBranch).to_string(),
+        })
+}
+
+/// 获取分支的构建信息
+pub fn fetch_build(build_url: &str, branch: &BranchInfo) -> Result<Build> {
+    let url = format!(
+        "{}?branch={}&package_id={}&password={}",
+        build_url, branch.branch, branch.package_id, branch.password
+    );
+    fetch_api(&url)
+}
+
+fn decompress(data: &[u8], compression: i32, source: &str) -> Result<Vec<u8>> {
+    if compression != 1 {
+        return Ok(data.to_vec());
+    }
+    zstd::decode_all(data).map_err(|e| Error::Download {
+        url: source.to_string(),
+        message: t!(ZstdFailed, e),
+    })
+}
+
+/// 下载并解析清单
+pub fn fetch_manifest(manifest: &BuildManifest) -> Result<SophonManifest> {
+    let url = manifest.manifest_download.url(&manifest.manifest.id);
+    let data = decompress(&fetch_bytes(&url)?, manifest.manifest_download.compression, &url)?;
+    SophonManifest::decode(data.as_slice()).map_err(|e| Error::Schema {
+        field: format!("manifest {}", manifest.manifest.id),
+        message: e.to_string(),
+    })
+}
+
+/// 读取本地文件中 `offset` 起 `len` 字节的 md5
+fn range_md5(file: &mut File, offset: u64, len: u64) -> std::io::Result<String> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut context = md5::Context::new();
+    let mut remaining = len;
+    let mut buffer = [0; 64 * 1024];
+    while remaining > 0 {
+        let want = remaining.min(buffer.len() as u64) as usize;
+        let read = file.read(&mut buffer[..want])?;
+        if read == 0 {
+            break;
+        }
+        context.consume(&buffer[..read]);
+        remaining -= read as u64;
+    }
+    Ok(format!("{:x}", context.compute()))
+}
+
+/// 对比本地文件，找出每个文件需要下载的分块
+pub fn plan_files(manifest: &SophonManifest, game_dir: &Path) -> Result<SophonPlan> {
+    let mut plan = SophonPlan::default();
+
+    for file in manifest.files.iter().filter(|file| !file.is_dir()) {
+        let local_path = file.local_path(game_dir)?;
+        let local_len = fs::metadata(&local_path).map(|meta| meta.len()).ok();
+
+        if local_len == Some(file.file_size()?) && file_md5(&local_path)?.eq_ignore_ascii_case(&file.md5) {
+            plan.unchanged += 1;
+            continue;
+        }
+
+        let mut local = match local_len {
+            Some(_) => Some(File::open(&local_path).with_path(&local_path)?),
+            None => None,
+        };
+
+        let mut file_plan = FilePlan { file: file.clone(), missing: Vec::new(), reused: Vec::new() };
+        for chunk in &file.chunks {
+            // 计划阶段校验分块的名称、大小与偏移，之后下载与组装不会再遇到无效值
+            chunk.file_name()?;
+            let end = chunk.end()?;
+            chunk.compressed_len()?;
+            let reusable = match (&mut local, local_len) {
+                (Some(local), Some(len)) if len >= end => range_md5(local, chunk.start()?, chunk.decompressed_len()?)
+                    .with_path(&local_path)?
+                    .eq_ignore_ascii_case(&chunk.decompressed_md5),
+                _ => false,
+            };
+            if reusable {
+                file_plan.reused.push(chunk.clone());
+            } else {
+                file_plan.missing.push(chunk.clone());
+            }
+        }
+        plan.files.push(file_plan);
+    }
+
+    Ok(plan)
+}
+
+/// 下载分块到 `chunk_dir`，已存在时跳过
+fn fetch_chunk(
+    chunk: &SophonChunk,
+    chunk_download: &DownloadInfo,
+    chunk_dir: &Path,
+    settings: &DownloadSettings,
+    progress: &dyn ProgressSink,
+) -> Result<()> {
+    let chunk_path = chunk.local_path(chunk_dir)?;
+    if fs::metadata(&chunk_path).is_ok_and(|meta| chunk.compressed_len().is_ok_and(|len| meta.len() == len)) {
+        return Ok(());
+    }
+
+    let url = chunk_download.url(&chunk.name);
+    let mut last_err = None;
+    for candidate in settings.candidate_urls(&url) {
+        // 分块较小，失败时从头下载
+        let _ = fs::remove_file(&chunk_path);
+        match download_with_resume(&candidate, &chunk_path.to_string_lossy(), settings.retries.max(1), None, &NoopSink) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                progress.suspend(&mut || warn!(url = %candidate, error = %e, "{}", t!(ChunkFailedNext)));
+                last_err = Some(e);
+            }
+        }
+    }
+    Err(last_err.expect("candidate_urls is never empty"))
+}
+
+/// 读取已下载的分块，校验并解压
+fn read_chunk(chunk: &SophonChunk, chunk_download: &DownloadInfo, chunk_dir: &Path) -> Result<Vec<u8>> {
+    let chunk_path = chunk.local_path(chunk_dir)?;
+    let data = fs::read(&chunk_path).with_path(&chunk_path)?;
+    if !chunk.compressed_md5.is_empty() {
+        let actual = format!("{:x}", md5::compute(&data));
+        if !actual.eq_ignore_ascii_case(&chunk.compressed_md5) {
+            let _ = fs::remove_file(&chunk_path);
+            return Err(Error::Checksum { path: chunk_path, expected: chunk.compressed_md5.clone(), actual });
+        }
+    }
+
+    let data = decompress(&data, chunk_download.compression, &chunk_download.url(&chunk.name))?;
+    let actual = format!("{:x}", md5::compute(&data));
+    if !actual.eq_ignore_ascii_case(&chunk.decompressed_md5) {
+        let _ = fs::remove_file(&chunk_path);
+        return Err(Error::Checksum { path: chunk_path, expected: chunk.decompressed_md5.clone(), actual });
+    }
+
+    Ok(data)
+}
+
+/// 下载计划中缺失的分块
+pub fn download_chunks(
+    plan: &SophonPlan,
+    chunk_download: &DownloadInfo,
+    chunk_dir: &Path,
+    settings: &DownloadSettings,
+    progress: &dyn ProgressSink,
+) -> Result<()> {
+    fs::create_dir_all(chunk_dir).with_path(chunk_dir)?;
+
+    let total = plan.download_bytes()?;
+    let mut downloaded = 0u64;
+    let mut fetched = HashSet::new();
+
+    progress.emit(Event::PhaseStarted { phase: Phase::Download, total });
+    for chunk in plan.files.iter().flat_map(|plan| &plan.missing) {
+        if !fetched.insert(chunk.name.as_str()) {
+            continue;
+        }
+        fetch_chunk(chunk, chunk_download, chunk_dir, settings, progress)?;
+        downloaded += chunk.compressed_len()?;
+        progress.emit(Event::BytesDownloaded { url: chunk_download.url(&chunk.name), downloaded, total });
+    }
+    progress.emit(Event::PhaseFinished { phase: Phase::Download });
+
+    Ok(())
+}
+
+/// 用本地旧文件与已下载的分块组装文件，校验后替换游戏目录中的旧文件，`owner` 不为空时交给该属主
+pub fn assemble_files(
+    plan: &SophonPlan,
+    chunk_download: &DownloadInfo,
+    game_dir: &Path,
+    chunk_dir: &Path,
+    owner: Option<Owner>,
+    progress: &dyn ProgressSink,
+) -> Result<UpdateStats> {
+    let mut stats = UpdateStats::default();
+    let mut modes = ModeGuard::new().with_owner(owner);
+
+    progress.emit(Event::PhaseStarted { phase: Phase::Assemble, total: plan.files.len() as u64 });
+    for file_plan in &plan.files {
+        let file = &file_plan.file;
+        let target_path = file.local_path(game_dir)?;
+        let tmp_path = PathBuf::from(format!("{}{}", target_path.display(), TMP_SUFFIX));
+        modes.prepare(&target_path).with_path(&target_path)?;
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent).with_path(parent)?;
+        }
+
+        let mut out = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&tmp_path)
+            .with_path(&tmp_path)?;
+        out.set_len(file.file_size()?).with_path(&tmp_path)?;
+
+        if !file_plan.reused.is_empty() {
+            let mut local = File::open(&target_path).with_path(&target_path)?;
+            for chunk in &file_plan.reused {
+                let len = usize::try_from(chunk.decompressed_len()?)
+                    .map_err(|_| invalid(&chunk.name, t!(ManifestInvalidValue, "decompressed_size", chunk.decompressed_size)))?;
+                let mut data = vec![0; len];
+                local.seek(SeekFrom::Start(chunk.start()?)).with_path(&target_path)?;
+                local.read_exact(&mut data).with_path(&target_path)?;
+                out.seek(SeekFrom::Start(chunk.start()?)).with_path(&tmp_path)?;
+                out.write_all(&data).with_path(&tmp_path)?;
+            }
+        }
+
+        for chunk in &file_plan.missing {
+            let data = read_chunk(chunk, chunk_download, chunk_dir)?;
+            out.seek(SeekFrom::Start(chunk.start()?)).with_path(&tmp_path)?;
+            out.write_all(&data).with_path(&tmp_path)?;
+        }
+        out.flush().with_path(&tmp_path)?;
+        drop(out);
+
+        if !file.md5.is_empty() {
+            let actual = file_md5(&tmp_path)?;
+            if !actual.eq_ignore_ascii_case(&file.md5) {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(Error::Checksum { path: target_path, expected: file.md5.clone(), actual });
+            }
+        }
+
+        fs::rename(&tmp_path, &target_path).map_err(|source| Error::Copy { path: target_path.clone(), source })?;
+        debug!(file = %file.name, reused = file_plan.reused.len(), downloaded = file_plan.missing.len(), "{}", t!(FileAssembled));
+        // 组装好的文件直接放入游戏目录，计入复制数
+        stats.copied += 1;
+        stats.touched.push(file.name.clone());
+        progress.emit(Event::FileAssembled { path: file.name.clone() });
+    }
+    progress.emit(Event::PhaseFinished { phase: Phase::Assemble });
+    modes.restore()?;
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn chunk(name: &str, data: &[u8], offset: i64) -> (SophonChunk, Vec<u8>) {
+        let compressed = zstd::encode_all(data, 0).unwrap();
+        let chunk = SophonChunk {
+            name: name.to_string(),
+            decompressed_md5: format!("{:x}", md5::compute(data)),
+            offset,
+            compressed_size: compressed.len() as i64,
+            decompressed_size: data.len() as i64,
+            compressed_xxh: 0,
+            compressed_md5: format!("{:x}", md5::compute(&compressed)),
+        };
+        (chunk, compressed)
+    }
+
+    fn sophon_file(name: &str, content: &[u8], chunks: Vec<SophonChunk>) -> SophonFile {
+        SophonFile {
+            name: name.to_string(),
+            chunks,
+            flags: 0,
+            size: content.len() as i64,
+            md5: format!("{:x}", md5::compute(content)),
+        }
+    }
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let (c, _) = chunk("c1", b"hello", 0);
+        let manifest = SophonManifest { files: vec![sophon_file("a.txt", b"hello", vec![c])] };
+        let decoded = SophonManifest::decode(manifest.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded, manifest);
+    }
+
+    #[test]
+    fn test_plan_rejects_escaping_paths_and_negative_sizes() {
+        let game_dir = TempDir::new().unwrap();
+        for name in ["../outside.txt", "/etc/passwd", "dir/../../outside.txt", ""] {
+            let (c, _) = chunk("c1", b"hello", 0);
+            let manifest = SophonManifest { files: vec![sophon_file(name, b"hello", vec![c])] };
+            assert!(matches!(plan_files(&manifest, game_dir.path()), Err(Error::Manifest { .. })), "{}", name);
+        }
+
+        let (mut c, _) = chunk("c1", b"hello", 0);
+        c.offset = -1;
+        let manifest = SophonManifest { files: vec![sophon_file("a.txt", b"hello", vec![c])] };
+        assert!(matches!(plan_files(&manifest, game_dir.path()), Err(Error::Manifest { .. })));
+
+        for name in ["../../x", "/etc/x", "sub/c1", ".."] {
+            let (c, _) = chunk(name, b"hello", 0);
+            let manifest = SophonManifest { files: vec![sophon_file("a.txt", b"hello", vec![c])] };
+            assert!(matches!(plan_files(&manifest, game_dir.path()), Err(Error::Manifest { .. })), "{}", name);
+        }
+
+        let mut file = sophon_file("a.txt", b"hello", Vec::new());
+        file.size = -5;
+        fs::write(game_dir.path().join("a.txt"), b"hello").unwrap();
+        assert!(matches!(plan_files(&SophonManifest { files: vec![file] }, game_dir.path()), Err(Error::Manifest { .. })));
+    }
+
+    #[test]
+    fn test_plan_and_apply_downloads_only_missing_chunks() {
+        let game_dir = TempDir::new().unwrap();
+        let chunk_dir = TempDir::new().unwrap();
+
+        // 未变化的文件
+        fs::write(game_dir.path().join("same.txt"), b"same").unwrap();
+        let (same_chunk, _) = chunk("same", b"same", 0);
+
+        // 前半部分未变化、后半部分变化的文件
+        fs::write(game_dir.path().join("changed.txt"), b"keep-old!").unwrap();
+        let (keep_chunk, _) = chunk("keep", b"keep-", 0);
+        let (new_chunk, new_data) = chunk("new", b"new!", 5);
+
+        // 新文件
+        let (fresh_chunk, fresh_data) = chunk("fresh", b"fresh", 0);
+
+        let manifest = SophonManifest {
+            files: vec![
+                sophon_file("same.txt", b"same", vec![same_chunk]),
+                sophon_file("changed.txt", b"keep-new!", vec![keep_chunk, new_chunk]),
+                sophon_file("dir/fresh.txt", b"fresh", vec![fresh_chunk]),
+            ],
+        };
+
+        let plan = plan_files(&manifest, game_dir.path()).unwrap();
+        assert_eq!(plan.unchanged, 1);
+        assert_eq!(plan.files.len(), 2);
+        assert_eq!(plan.files[0].reused.len(), 1);
+        assert_eq!(plan.download_bytes().unwrap(), (new_data.len() + fresh_data.len()) as u64);
+
+        let mut server = mockito::Server::new();
+        let _new = server.mock("GET", "/chunks/new").with_body(&new_data).expect(1).create();
+        let _fresh = server.mock("GET", "/chunks/fresh").with_body(&fresh_data).expect(1).create();
+        let _keep = server.mock("GET", "/chunks/keep").expect(0).create();
+
+        let chunk_download = DownloadInfo {
+            compression: 1,
+            url_prefix: format!("{}/chunks", server.url()),
+            url_suffix: String::new(),
+        };
+        download_chunks(&plan, &chunk_download, chunk_dir.path(), &DownloadSettings::default(), &NoopSink).unwrap();
+        let stats = assemble_files(&plan, &chunk_download, game_dir.path(), chunk_dir.path(), None, &NoopSink).unwrap();
+
+        assert_eq!(stats.copied, 2);
+        assert_eq!(fs::read(game_dir.path().join("changed.txt")).unwrap(), b"keep-new!");
+        assert_eq!(fs::read(game_dir.path().join("dir/fresh.txt")).unwrap(), b"fresh");
+        _new.assert();
+        _fresh.assert();
+        _keep.assert();
+    }
+}
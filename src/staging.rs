@@ -0,0 +1,499 @@
+//! 暂存区：把所有更新包解压到同一目录，合并补丁与删除列表后一次性应用到游戏目录
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::error::{Error, IoContext, Result};
+use crate::progress::{Event, Phase, ProgressSink};
+use crate::settings::CopyErrorPolicy;
+use crate::permissions::{ModeGuard, Owner};
+use crate::util::{parse_line_json, UpdateStats};
+use crate::t;
+
+/// 暂存目录的名称，建在游戏目录或配置的位置下；更新器只会清空这个子目录
+pub const STAGING_DIR: &str = "_updater_staging";
+
+const HDIFF_LIST: &str = "hdifffiles.txt";
+const DELETE_LIST: &str = "deletefiles.txt";
+
+/// 已解压、等待应用的更新包集合
+#[derive(Debug)]
+pub struct Staging {
+    dir: PathBuf,
+    /// 暂存文件 → 来源更新包
+    owners: HashMap<String, String>,
+    /// 需要打补丁的文件与来源更新包
+    hdiff_files: Vec<(String, String)>,
+    /// 需要删除的文件与来源更新包
+    delete_files: Vec<(String, String)>,
+    packages: u64,
+    on_copy_error: CopyErrorPolicy,
+    copy_retries: u8,
+    /// 暂存目录与游戏目录中涉及的文件的权限，写入的文件交给指定的属主
+    modes: ModeGuard,
+}
+
+/// 文件放入游戏目录的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Placement {
+    Rename,
+    Reflink,
+    Copy,
+}
+
+/// 把暂存文件放到 `dest`：优先重命名，跨文件系统时尝试 reflink，最后才逐字节复制
+///
+/// reflink 与复制先写入同目录的临时文件再重命名，目标文件不会处于写了一半的状态
+pub fn place_file(source: &Path, dest: &Path) -> io::Result<Placement> {
+    if fs::rename(source, dest).is_ok() {
+        return Ok(Placement::Rename);
+    }
+
+    let mut tmp_name = dest.as_os_str().to_owned();
+    tmp_name.push(".placing");
+    let tmp_path = PathBuf::from(tmp_name);
+    let _ = fs::remove_file(&tmp_path);
+
+    let placement = match reflink_copy::reflink_or_copy(source, &tmp_path) {
+        Ok(None) => Placement::Reflink,
+        Ok(Some(_)) => Placement::Copy,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+    };
+    fs::rename(&tmp_path, dest).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })?;
+
+    Ok(placement)
+}
+
+/// 放置文件，`retry` 策略下失败后重试
+fn place_with_policy(source: &Path, dest: &Path, policy: CopyErrorPolicy, retries: u8, progress: &dyn ProgressSink) -> io::Result<Placement> {
+    let mut attempt = 0;
+    loop {
+        match place_file(source, dest) {
+            Ok(placement) => return Ok(placement),
+            Err(e) if policy == CopyErrorPolicy::Retry && attempt < retries => {
+                attempt += 1;
+                progress.suspend(&mut || warn!(path = %dest.display(), error = %e, attempt, "{}", t!(PlaceRetry)));
+                thread::sleep(Duration::from_secs(1));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn package_name(archive_path: &Path) -> String {
+    archive_path
+        .file_name()
+        .map_or_else(|| archive_path.display().to_string(), |name| name.to_string_lossy().to_string())
+}
+
+fn conflict(path: &str, first: &str, second: &str) -> Error {
+    Error::Plan(t!(PackageConflict, path, first, second))
+}
+
+impl Staging {
+    /// 在 `parent` 下创建空的暂存目录，清除上次残留的内容；`parent` 中的其他文件不受影响
+    ///
+    /// 暂存目录与放置到游戏目录的文件交给 `owner`，为空时不修改属主
+    pub fn new(parent: impl AsRef<Path>, owner: Option<Owner>) -> Result<Self> {
+        let dir = parent.as_ref().join(STAGING_DIR);
+        // 暂存目录默认在游戏目录下，只读的游戏目录也要先放宽权限
+        let mut modes = ModeGuard::new().with_owner(owner);
+        modes.prepare(&dir).with_path(&dir)?;
+        if dir.exists() {
+            fs::remove_dir_all(&dir).with_path(&dir)?;
+        }
+        fs::create_dir_all(&dir).with_path(&dir)?;
+        modes.created(&dir);
+
+        Ok(Staging {
+            dir,
+            owners: HashMap::new(),
+            hdiff_files: Vec::new(),
+            delete_files: Vec::new(),
+            packages: 0,
+            on_copy_error: CopyErrorPolicy::default(),
+            copy_retries: 0,
+            modes,
+        })
+    }
+
+    /// 设置文件放置失败时的处理方式
+    pub fn with_copy_policy(mut self, policy: CopyErrorPolicy, retries: u8) -> Self {
+        self.on_copy_error = policy;
+        self.copy_retries = retries;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 解压更新包并合并其补丁与删除列表，与已暂存的更新包冲突时报错
+    pub fn add_package(&mut self, archive_path: &Path, progress: &dyn ProgressSink) -> Result<()> {
+        let package = package_name(archive_path);
+
+        info!(archive = %archive_path.display(), "{}", t!(Extracting));
+        let extract_err = |source| Error::Extract { path: archive_path.to_path_buf(), source };
+        let zipfile = File::open(archive_path).with_path(archive_path)?;
+        let mut archive = zip::ZipArchive::new(zipfile).map_err(extract_err)?;
+
+        let file_count = archive.len();
+        progress.emit(Event::PhaseStarted { phase: Phase::Extract, total: file_count as u64 });
+
+        for i in 0..file_count {
+            let mut file = archive.by_index(i).map_err(extract_err)?;
+            // 跳过试图逃逸解压目录的条目
+            let (outpath, name) = match file.enclosed_name() {
+                Some(path) => (self.dir.join(path), path.to_string_lossy().replace('\\', "/")),
+                None => {
+                    progress.emit(Event::FileExtracted { path: file.name().to_string() });
+                    continue;
+                }
+            };
+
+            // 创建文件夹结构
+            if file.is_dir() {
+                fs::create_dir_all(&outpath).with_path(&outpath)?;
+            } else {
+                // 列表文件读取后即删除，不会互相覆盖
+                if name != HDIFF_LIST && name != DELETE_LIST {
+                    if let Some(owner) = self.owners.get(&name) {
+                        return Err(conflict(&name, owner, &package));
+                    }
+                    self.owners.insert(name.clone(), package.clone());
+                }
+
+                if let Some(p) = outpath.parent() {
+                    if !p.exists() {
+                        fs::create_dir_all(p).with_path(p)?;
+                    }
+                }
+                let mut outfile = File::create(&outpath).with_path(&outpath)?;
+                io::copy(&mut file, &mut outfile).map_err(|e| extract_err(e.into()))?;
+            }
+
+            progress.emit(Event::FileExtracted { path: name });
+        }
+        progress.emit(Event::PhaseFinished { phase: Phase::Extract });
+
+        let hdiff_files_path = self.dir.join(HDIFF_LIST);
+        if hdiff_files_path.exists() {
+            for remote_name in parse_line_json(&hdiff_files_path)? {
+                if let Some((_, owner)) = self.hdiff_files.iter().find(|(name, _)| name == &remote_name) {
+                    return Err(conflict(&remote_name, owner, &package));
+                }
+                self.hdiff_files.push((remote_name, package.clone()));
+            }
+            fs::remove_file(&hdiff_files_path).with_path(&hdiff_files_path)?;
+        }
+
+        let delete_files_path = self.dir.join(DELETE_LIST);
+        if delete_files_path.exists() {
+            let data = fs::read_to_string(&delete_files_path).with_path(&delete_files_path)?;
+            self.delete_files.extend(
+                data.lines()
+                    .map(str::trim)
+                    .filter(|path| !path.is_empty())
+                    .map(|path| (path.to_string(), package.clone())),
+            );
+            fs::remove_file(&delete_files_path).with_path(&delete_files_path)?;
+        }
+
+        self.packages += 1;
+        self.check_deletes()
+    }
+
+    /// 一个更新包要删除的文件不能由另一个更新包提供或打补丁
+    fn check_deletes(&self) -> Result<()> {
+        for (path, deleter) in &self.delete_files {
+            let provider = self.owners
+                .get(path)
+                .or_else(|| self.hdiff_files.iter().find(|(name, _)| name == path).map(|(_, owner)| owner));
+            if let Some(provider) = provider.filter(|provider| *provider != deleter) {
+                return Err(conflict(path, provider, deleter));
+            }
+        }
+        Ok(())
+    }
+
+    /// 依次打补丁、删除、放置文件，把暂存区应用到游戏目录，完成后删除暂存区
+    ///
+    /// 只放宽涉及的文件与目录的权限，结束后恢复
+    pub fn apply(self, game_dir: &Path, progress: &dyn ProgressSink) -> Result<UpdateStats> {
+        let mut stats = UpdateStats { packages: self.packages, ..UpdateStats::default() };
+        let update_dir = self.dir.as_path();
+        let (on_copy_error, copy_retries) = (self.on_copy_error, self.copy_retries);
+        let mut modes = self.modes;
+
+        // 获取游戏安装目录路径
+        let genshin_root = game_dir;
+
+        // 1. 处理所有更新包的 hdifffiles.txt
+        if !self.hdiff_files.is_empty() {
+            progress.emit(Event::PhaseStarted { phase: Phase::Patch, total: self.hdiff_files.len() as u64 });
+
+            for (remote_name, _) in self.hdiff_files {
+                let hdiff_path = update_dir.join(format!("{}.hdiff", remote_name));
+                let target_path = genshin_root.join(&remote_name);
+                let dest_path = update_dir.join(&remote_name);
+
+                if !target_path.exists() {
+                    progress.suspend(&mut || warn!(path = %target_path.display(), "{}", t!(SkipMissingFile)));
+                    progress.emit(Event::FilePatched { path: remote_name });
+                    continue;
+                }
+                modes.prepare(&target_path).with_path(&target_path)?;
+
+                let output = Command::new("./hpatchz")
+                    .arg(&target_path)
+                    .arg(&hdiff_path)
+                    .arg(&dest_path)
+                    .output()
+                    .with_path("./hpatchz")?;
+
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if !output.status.success() {
+                    progress.suspend(&mut || tracing::error!(file = %remote_name, status = %output.status, stderr = %stderr.trim(), "{}", t!(PatchFailed)));
+                    return Err(Error::Patch {
+                        file: remote_name,
+                        code: output.status.code(),
+                        stderr: stderr.trim().to_string(),
+                    });
+                }
+                debug!(file = %remote_name, status = %output.status, stderr = %stderr.trim(), "{}", t!(HpatchzDone));
+
+                fs::remove_file(&hdiff_path).with_path(&hdiff_path)?;
+                stats.patched += 1;
+                progress.emit(Event::FilePatched { path: remote_name });
+            }
+
+            progress.emit(Event::PhaseFinished { phase: Phase::Patch });
+        }
+
+        // 2. 处理所有更新包的 deletefiles.txt
+        if !self.delete_files.is_empty() {
+            progress.emit(Event::PhaseStarted { phase: Phase::Delete, total: self.delete_files.len() as u64 });
+            for (path, _) in self.delete_files {
+                let delete_path = genshin_root.join(&path);
+                if delete_path.exists() {
+                    progress.suspend(&mut || info!(path, "{}", t!(Deleting)));
+                    modes.prepare(&delete_path).with_path(&delete_path)?;
+                    fs::remove_file(&delete_path)
+                        .or_else(|_| fs::remove_dir_all(&delete_path))
+                        .map_err(|source| Error::Delete { path: delete_path.clone(), source })?;
+                    stats.deleted += 1;
+                }
+                progress.emit(Event::FileDeleted { path });
+            }
+            progress.emit(Event::PhaseFinished { phase: Phase::Delete });
+        }
+
+        info!("{}", t!(Placing));
+        // 跳过补丁文件
+        let skip_files = [".hdiff"];
+
+        // 使用 walkdir 遍历目录
+        let all_files: Vec<_> = walkdir::WalkDir::new(update_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| {
+                let p = e.path().to_string_lossy();
+                !skip_files.iter().any(|ext| p.ends_with(ext))
+            })
+            .collect();
+
+        progress.emit(Event::PhaseStarted { phase: Phase::Copy, total: all_files.len() as u64 });
+        let mut placements = HashMap::new();
+
+        for entry in all_files {
+            let source_path = entry.path();
+            let Ok(relative_path) = source_path.strip_prefix(update_dir) else {
+                continue;
+            };
+            let dest_path = game_dir.join(relative_path);
+            modes.prepare(&dest_path).with_path(&dest_path)?;
+
+            if let Some(parent) = dest_path.parent() {
+                fs::create_dir_all(parent).with_path(parent)?;
+            }
+
+            let placement = match place_with_policy(source_path, &dest_path, on_copy_error, copy_retries, progress) {
+                Ok(placement) => Some(placement),
+                Err(source) if on_copy_error == CopyErrorPolicy::Skip => {
+                    progress.suspend(&mut || warn!(path = %dest_path.display(), error = %source, "{}", t!(PlaceSkipped)));
+                    None
+                }
+                Err(source) => return Err(Error::Copy { path: dest_path, source }),
+            };
+            let remote_name = relative_path.to_string_lossy().replace('\\', "/");
+            let Some(placement) = placement else {
+                stats.skipped.push(remote_name.clone());
+                progress.emit(Event::FileCopied { path: remote_name });
+                continue;
+            };
+            debug!(from = %source_path.display(), to = %dest_path.display(), ?placement, "{}", t!(FilePlaced));
+            *placements.entry(placement).or_insert(0u64) += 1;
+
+            stats.copied += 1;
+            stats.touched.push(remote_name.clone());
+            progress.emit(Event::FileCopied { path: remote_name });
+        }
+        progress.emit(Event::PhaseFinished { phase: Phase::Copy });
+        info!(
+            renamed = placements.get(&Placement::Rename).copied().unwrap_or(0),
+            reflinked = placements.get(&Placement::Reflink).copied().unwrap_or(0),
+            copied = placements.get(&Placement::Copy).copied().unwrap_or(0),
+            "{}",
+            t!(PlaceDone)
+        );
+
+
+        info!("{}", t!(CleaningUp));
+        progress.emit(Event::PhaseStarted { phase: Phase::Cleanup, total: 0 });
+        // 先删除暂存目录再恢复权限，只读的游戏目录恢复后就删不掉了
+        fs::remove_dir_all(update_dir).with_path(update_dir)?;
+        modes.restore()?;
+        progress.emit(Event::PhaseFinished { phase: Phase::Cleanup });
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use crate::progress::NoopSink;
+    use crate::testing::zip_bytes;
+
+    #[test]
+    fn test_merges_packages_into_one_apply() {
+        let temp_dir = TempDir::new().unwrap();
+        let game_dir = temp_dir.path().join("game");
+        fs::create_dir_all(&game_dir).unwrap();
+        fs::write(game_dir.join("old.dat"), "old").unwrap();
+        fs::write(game_dir.join("old_voice.pck"), "old").unwrap();
+
+        let game_zip = temp_dir.path().join("game.zip");
+        fs::write(&game_zip, zip_bytes(&[("new.dat", "new"), (DELETE_LIST, "old.dat\n")])).unwrap();
+        let audio_zip = temp_dir.path().join("en-us.zip");
+        fs::write(&audio_zip, zip_bytes(&[("voice.pck", "voice"), (DELETE_LIST, "old_voice.pck\n")])).unwrap();
+
+        let mut staging = Staging::new(temp_dir.path(), None).unwrap();
+        staging.add_package(&game_zip, &NoopSink).unwrap();
+        staging.add_package(&audio_zip, &NoopSink).unwrap();
+        let stats = staging.apply(&game_dir, &NoopSink).unwrap();
+
+        assert_eq!(stats.packages, 2);
+        assert_eq!(stats.deleted, 2);
+        assert_eq!(stats.copied, 2);
+        assert!(!game_dir.join("old.dat").exists());
+        assert!(!game_dir.join("old_voice.pck").exists());
+        assert_eq!(fs::read_to_string(game_dir.join("voice.pck")).unwrap(), "voice");
+    }
+
+    #[test]
+    fn test_rejects_conflicting_packages() {
+        let temp_dir = TempDir::new().unwrap();
+        let game_zip = temp_dir.path().join("game.zip");
+        fs::write(&game_zip, zip_bytes(&[("shared.dat", "a")])).unwrap();
+        let audio_zip = temp_dir.path().join("en-us.zip");
+        fs::write(&audio_zip, zip_bytes(&[(DELETE_LIST, "shared.dat\n")])).unwrap();
+        let other_zip = temp_dir.path().join("ja-jp.zip");
+        fs::write(&other_zip, zip_bytes(&[("shared.dat", "b")])).unwrap();
+
+        let mut staging = Staging::new(temp_dir.path(), None).unwrap();
+        staging.add_package(&game_zip, &NoopSink).unwrap();
+        let err = staging.add_package(&audio_zip, &NoopSink).unwrap_err();
+        assert!(err.to_string().contains("shared.dat"));
+
+        let mut staging = Staging::new(temp_dir.path(), None).unwrap();
+        staging.add_package(&game_zip, &NoopSink).unwrap();
+        assert!(staging.add_package(&other_zip, &NoopSink).is_err());
+    }
+
+    #[test]
+    fn test_new_only_clears_its_own_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("keep.txt"), "keep").unwrap();
+        fs::create_dir(temp_dir.path().join(STAGING_DIR)).unwrap();
+        fs::write(temp_dir.path().join(STAGING_DIR).join("stale.dat"), "stale").unwrap();
+
+        let staging = Staging::new(temp_dir.path(), None).unwrap();
+        assert_eq!(staging.dir(), temp_dir.path().join(STAGING_DIR));
+        assert!(!staging.dir().join("stale.dat").exists());
+        assert!(temp_dir.path().join("keep.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stages_inside_read_only_game_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let game_dir = temp_dir.path().join("game");
+        fs::create_dir_all(&game_dir).unwrap();
+        let game_zip = temp_dir.path().join("game.zip");
+        fs::write(&game_zip, zip_bytes(&[("new.dat", "new")])).unwrap();
+        fs::set_permissions(&game_dir, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let mut staging = Staging::new(&game_dir, None).unwrap();
+        staging.add_package(&game_zip, &NoopSink).unwrap();
+        staging.apply(&game_dir, &NoopSink).unwrap();
+
+        assert_eq!(fs::read_to_string(game_dir.join("new.dat")).unwrap(), "new");
+        assert!(!game_dir.join(STAGING_DIR).exists());
+        assert_eq!(fs::metadata(&game_dir).unwrap().permissions().mode() & 0o777, 0o555);
+        fs::set_permissions(&game_dir, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_place_file_replaces_existing() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("staged.dat");
+        let dest = temp_dir.path().join("game.dat");
+        fs::write(&source, "new").unwrap();
+        fs::write(&dest, "old").unwrap();
+
+        assert_eq!(place_file(&source, &dest).unwrap(), Placement::Rename);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "new");
+        assert!(!source.exists());
+        assert!(place_file(&source, &dest).is_err());
+    }
+
+    #[test]
+    fn test_copy_error_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let game_dir = temp_dir.path().join("game");
+        // 目标位置被非空目录占用，无法放置
+        fs::create_dir_all(game_dir.join("blocked.dat/inner")).unwrap();
+
+        let game_zip = temp_dir.path().join("game.zip");
+        fs::write(&game_zip, zip_bytes(&[("blocked.dat", "new"), ("ok.dat", "ok")])).unwrap();
+
+        let mut staging = Staging::new(temp_dir.path(), None).unwrap();
+        staging.add_package(&game_zip, &NoopSink).unwrap();
+        let err = staging.apply(&game_dir, &NoopSink).unwrap_err();
+        assert_eq!(err.exit_code(), 16);
+
+        let mut staging = Staging::new(temp_dir.path(), None)
+            .unwrap()
+            .with_copy_policy(CopyErrorPolicy::Skip, 0);
+        staging.add_package(&game_zip, &NoopSink).unwrap();
+        let stats = staging.apply(&game_dir, &NoopSink).unwrap();
+        assert_eq!(stats.skipped, vec!["blocked.dat"]);
+        assert_eq!(stats.touched, vec!["ok.dat"]);
+    }
+}
@@ -0,0 +1,13 @@
+//! 测试共用的辅助函数
+
+use std::io::{Cursor, Write};
+
+/// 生成包含 `files`（文件名与内容）的 zip 更新包
+pub fn zip_bytes<C: AsRef<[u8]>>(files: &[(&str, C)]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in files {
+        writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+        writer.write_all(content.as_ref()).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
@@ -0,0 +1,78 @@
+//! 记录下载速度，用于估计下载时间
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{IoContext, Result};
+
+pub const THROUGHPUT_FILE: &str = "throughput.json";
+
+/// 新样本在移动平均中的权重
+const SAMPLE_WEIGHT: f64 = 0.3;
+
+/// 太短的下载测不准速度，不记录
+const MIN_SAMPLE_BYTES: u64 = 1024 * 1024;
+const MIN_SAMPLE_TIME: Duration = Duration::from_secs(1);
+
+/// 最近的下载速度，按指数移动平均更新
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Throughput {
+    pub bytes_per_sec: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Throughput {
+    /// 按该速度下载 `bytes` 所需的时间
+    pub fn estimate(&self, bytes: u64) -> Duration {
+        Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec.max(1.0))
+    }
+}
+
+/// 读取记录的下载速度，不存在或无法解析时返回 `None`
+pub fn load(cache_dir: &Path) -> Option<Throughput> {
+    let data = fs::read_to_string(cache_dir.join(THROUGHPUT_FILE)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+/// 记录一次下载，返回更新后的速度；样本太小时不记录
+pub fn record(cache_dir: &Path, bytes: u64, elapsed: Duration) -> Result<Option<Throughput>> {
+    if bytes < MIN_SAMPLE_BYTES || elapsed < MIN_SAMPLE_TIME {
+        return Ok(None);
+    }
+
+    let sample = bytes as f64 / elapsed.as_secs_f64();
+    let bytes_per_sec = match load(cache_dir) {
+        Some(previous) => previous.bytes_per_sec * (1.0 - SAMPLE_WEIGHT) + sample * SAMPLE_WEIGHT,
+        None => sample,
+    };
+    let throughput = Throughput { bytes_per_sec, updated_at: Utc::now() };
+
+    fs::create_dir_all(cache_dir).with_path(cache_dir)?;
+    let path = cache_dir.join(THROUGHPUT_FILE);
+    let data = serde_json::to_string_pretty(&throughput).expect("throughput is serializable");
+    fs::write(&path, data).with_path(&path)?;
+
+    Ok(Some(throughput))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_moving_average() {
+        let temp_dir = TempDir::new().unwrap();
+        let mib = 1024 * 1024;
+        assert!(record(temp_dir.path(), 10, Duration::from_secs(5)).unwrap().is_none());
+        assert!(load(temp_dir.path()).is_none());
+
+        record(temp_dir.path(), 10 * mib, Duration::from_secs(1)).unwrap();
+        let throughput = record(temp_dir.path(), 20 * mib, Duration::from_secs(1)).unwrap().unwrap();
+        assert!((throughput.bytes_per_sec - 13.0 * mib as f64).abs() < 1.0);
+        assert_eq!(load(temp_dir.path()).unwrap().estimate(26 * mib).as_secs_f64().round(), 2.0);
+    }
+}
@@ -0,0 +1,1329 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::error::{Error, IoContext, Result};
+use crate::game_config::{read_game_version, write_game_version, CONFIG_FILE};
+use crate::language::{detect_installed_languages, is_language_code, language_name, InstalledLanguages};
+use crate::manifest_cache::{self, CachedManifest};
+use crate::orphans::{self, Orphan};
+use crate::package_cache::{CacheEntry, PackageCache};
+use crate::settings::Settings;
+use crate::sophon;
+use crate::throughput;
+use crate::parser::{GamePackage, Response};
+use crate::pkg_version::{pkg_version_files, read_all_pkg_versions, read_pkg_version, PkgEntry};
+use crate::progress::{Event, NoopSink, Phase, ProgressSink};
+use crate::permissions::{is_root, ModeGuard, Owner};
+use crate::util::{download_package, package_path, part_path, verify_md5, UpdateStats};
+use crate::staging::{Staging, STAGING_DIR};
+use crate::version::{find_hops, parse_patch_name, GameVersion, Route, UpgradePath};
+use crate::{t, API_URL, CACHE_DIR, UPDATE_DIR};
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, USER_AGENT};
+use reqwest::StatusCode;
+
+/// Sophon 分块在更新目录下的存放位置
+const SOPHON_CHUNK_DIR: &str = "sophon_chunks";
+
+/// 更新包类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackageKind {
+    Game,
+    Audio(String),
+}
+
+/// 计划中的单个更新包
+#[derive(Debug, Clone)]
+pub struct PlannedPackage {
+    pub kind: PackageKind,
+    pub url: String,
+    pub md5: String,
+    pub size: u64,
+    pub decompressed_size: u64,
+}
+
+/// 一次更新的完整计划
+#[derive(Debug, Clone)]
+pub struct UpdatePlan {
+    pub from_version: String,
+    pub to_version: String,
+    pub game_biz: String,
+    pub packages: Vec<PlannedPackage>,
+}
+
+/// 汇总表中的一个更新包
+#[derive(Debug, Clone)]
+pub struct SummaryRow {
+    pub from_version: String,
+    pub to_version: String,
+    pub kind: PackageKind,
+    pub size: u64,
+    /// 已在缓存（或下载目录）中的字节数
+    pub cached: u64,
+    pub decompressed_size: u64,
+}
+
+/// 更新前的汇总：各更新包的大小与缓存情况，以及预计的磁盘变化与下载时间
+#[derive(Debug, Clone)]
+pub struct PlanSummary {
+    pub rows: Vec<SummaryRow>,
+    /// 还需下载的字节数
+    pub download: u64,
+    /// 游戏目录与缓存的总大小变化，清单缺少解压后大小时为空
+    pub disk_change: Option<i64>,
+    /// 按记录的下载速度估计的下载时间，没有记录时为空
+    pub eta: Option<Duration>,
+}
+
+/// 安装校验结果
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub checked: usize,
+    pub missing: Vec<String>,
+    /// 大小（或 md5）不符的文件
+    pub mismatched: Vec<String>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// 更新器：获取清单、制定计划、下载、应用、校验
+pub struct Updater {
+    game_dir: PathBuf,
+    api_url: String,
+    cache_dir: PathBuf,
+    /// 无 md5 的更新包、Sophon 分块与修复文件的下载目录
+    update_dir: PathBuf,
+    offline: bool,
+    settings: Settings,
+    /// 指定的文件属主，覆盖自动检测
+    owner: Option<Owner>,
+    progress: Arc<dyn ProgressSink>,
+}
+
+impl Updater {
+    pub fn new(game_dir: impl Into<PathBuf>) -> Self {
+        Updater {
+            game_dir: game_dir.into(),
+            api_url: API_URL.to_string(),
+            cache_dir: PathBuf::from(CACHE_DIR),
+            update_dir: PathBuf::from(UPDATE_DIR),
+            offline: false,
+            settings: Settings::default(),
+            owner: None,
+            progress: Arc::new(NoopSink),
+        }
+    }
+
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressSink>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    pub fn with_cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = cache_dir.into();
+        self
+    }
+
+    pub fn with_update_dir(mut self, update_dir: impl Into<PathBuf>) -> Self {
+        self.update_dir = update_dir.into();
+        self
+    }
+
+    /// 离线模式：使用缓存的清单与已下载的更新包，不访问网络
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// 写入游戏目录的文件交给 `owner`，不再按游戏目录检测
+    pub fn with_owner(mut self, owner: Option<Owner>) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    pub fn game_dir(&self) -> &Path {
+        &self.game_dir
+    }
+
+    pub fn package_cache(&self) -> PackageCache {
+        PackageCache::new(&self.cache_dir)
+    }
+
+    pub fn progress(&self) -> &dyn ProgressSink {
+        self.progress.as_ref()
+    }
+
+    /// 游戏目录 `config.ini` 中记录的版本
+    pub fn installed_version(&self) -> Result<Option<String>> {
+        read_game_version(&self.game_dir)
+    }
+
+    /// 游戏目录中已安装的语音包
+    pub fn installed_languages(&self) -> Result<InstalledLanguages> {
+        detect_installed_languages(&self.game_dir)
+    }
+
+    /// 读取缓存的清单，不访问网络
+    pub fn cached_manifest(&self) -> Option<Response> {
+        let cached = manifest_cache::load(&self.cache_dir).filter(|cached| cached.url == self.api_url)?;
+        info!(fetched_at = %cached.fetched_at, "{}", t!(UsingCachedManifest));
+        Response::parse(&cached.body).ok()
+    }
+
+    /// 获取最新的安装包清单
+    /// 成功后缓存到磁盘；再次请求时带上条件请求头，未变化则直接使用缓存
+    pub fn fetch_manifest(&self) -> Result<Response> {
+        let cached = manifest_cache::load(&self.cache_dir).filter(|cached| cached.url == self.api_url);
+
+        if self.offline {
+            return self.cached_manifest().ok_or_else(|| Error::Api {
+                url: self.api_url.clone(),
+                message: t!(OfflineNoManifest).to_string(),
+            });
+        }
+
+        info!(url = %self.api_url, "{}", t!(FetchingManifest));
+        let api_err = |e: reqwest::Error| Error::Api { url: self.api_url.clone(), message: e.to_string() };
+
+        let mut request = reqwest::blocking::Client::new()
+            .get(&self.api_url)
+            .header(USER_AGENT, "genshin-updater");
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let res = request.send().map_err(api_err)?;
+        if res.status() == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                info!(fetched_at = %cached.fetched_at, "{}", t!(ManifestNotModified));
+                return Response::parse(&cached.body);
+            }
+        }
+
+        let res = res.error_for_status().map_err(api_err)?;
+        let header = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let body = res.text().map_err(api_err)?;
+
+        // 只缓存有效的清单
+        let response = Response::parse(&body)?;
+        let manifest = CachedManifest {
+            url: self.api_url.clone(),
+            fetched_at: chrono::Utc::now(),
+            etag,
+            last_modified,
+            body,
+        };
+        if let Err(e) = manifest_cache::save(&self.cache_dir, &manifest) {
+            warn!(error = %e, "{}", t!(ManifestCacheFailed));
+        }
+
+        Ok(response)
+    }
+
+    /// 根据清单、起始版本与语言制定更新计划
+    pub fn plan(&self, response: &Response, from_version: &str, languages: &[String]) -> Result<UpdatePlan> {
+        self.plan_game(response.game_package()?, from_version, languages)
+    }
+
+    /// 为清单中的指定游戏制定更新计划
+    pub fn plan_game(&self, game_package: &GamePackage, from_version: &str, languages: &[String]) -> Result<UpdatePlan> {
+        let patch = game_package.main.patches
+            .iter()
+            .find(|patch| patch.version == from_version)
+            .ok_or_else(|| Error::Plan(t!(NoPatchFrom, from_version)))?;
+
+        let game_pkg = patch.game_pkgs
+            .first()
+            .ok_or_else(|| Error::Plan(t!(PatchNoGamePkg, from_version)))?;
+
+        let mut packages = vec![PlannedPackage {
+            kind: PackageKind::Game,
+            url: game_pkg.url.clone(),
+            md5: game_pkg.md5.clone(),
+            size: game_pkg.size,
+            decompressed_size: game_pkg.decompressed_size,
+        }];
+
+        for language in languages {
+            let audio_pkg = patch.audio_pkgs
+                .iter()
+                .find(|pkg| pkg.language.eq_ignore_ascii_case(language))
+                .ok_or_else(|| Error::Plan(t!(PatchNoLanguage, from_version, language)))?;
+            packages.push(PlannedPackage {
+                kind: PackageKind::Audio(audio_pkg.language.clone()),
+                url: audio_pkg.url.clone(),
+                md5: audio_pkg.md5.clone(),
+                size: audio_pkg.size,
+                decompressed_size: audio_pkg.decompressed_size,
+            });
+        }
+
+        Ok(UpdatePlan {
+            from_version: from_version.to_string(),
+            to_version: game_package.main.major.version.clone(),
+            game_biz: game_package.game.biz.clone(),
+            packages,
+        })
+    }
+
+    /// 根据已安装版本选择升级路线：跳数最少的补丁链（清单中的补丁与缓存中的旧补丁），
+    /// 没有可用的补丁时下载完整安装包
+    pub fn plan_upgrade(&self, game_package: &GamePackage, installed: Option<GameVersion>, languages: &[String]) -> Result<UpgradePath> {
+        let latest: GameVersion = game_package.main.major.version.parse()?;
+        let Some(installed) = installed else {
+            return self.plan_full(game_package, None, languages, t!(RouteFullUnknown, latest));
+        };
+        if installed >= latest {
+            return Err(Error::Plan(t!(NotOlderThanLatest, installed, latest)));
+        }
+
+        let mut steps = Vec::new();
+        for patch in &game_package.main.patches {
+            let Ok(from) = patch.version.parse::<GameVersion>() else {
+                warn!(version = %patch.version, "{}", t!(UnknownPatchVersion));
+                continue;
+            };
+            // 缺少所选语言的补丁不能使用
+            if let Ok(plan) = self.plan_game(game_package, &patch.version, languages) {
+                steps.push((from, latest, plan));
+            }
+        }
+        steps.extend(self.cached_patches(&game_package.game.biz, languages)?);
+
+        let edges: Vec<(GameVersion, GameVersion)> = steps.iter().map(|(from, to, _)| (*from, *to)).collect();
+        let Some(hops) = find_hops(installed, latest, &edges) else {
+            return self.plan_full(game_package, Some(installed), languages, t!(RouteFull, installed, latest, latest));
+        };
+
+        let (route, reason) = if hops.len() == 1 {
+            (Route::Direct, t!(RouteDirect, installed, latest))
+        } else {
+            let chain: Vec<String> = std::iter::once(installed)
+                .chain(hops.iter().map(|&idx| edges[idx].1))
+                .map(|version| version.to_string())
+                .collect();
+            (Route::MultiHop, t!(RouteMultiHop, installed, chain.join(" → ")))
+        };
+        let steps = hops.into_iter().map(|idx| steps[idx].2.clone()).collect();
+
+        Ok(UpgradePath { route, steps, reason })
+    }
+
+    /// 缓存中包含游戏与所选语言的旧补丁，按起始与目标版本分组
+    fn cached_patches(&self, game_biz: &str, languages: &[String]) -> Result<Vec<(GameVersion, GameVersion, UpdatePlan)>> {
+        let mut groups: HashMap<(GameVersion, GameVersion), Vec<(PackageKind, CacheEntry)>> = HashMap::new();
+        // 未下载完成或不知道下载地址的更新包校验失败后无法重新下载，不参与规划
+        for entry in self.package_cache().list()?.into_iter().filter(CacheEntry::is_usable) {
+            if let Some((kind, from, to)) = parse_patch_name(&entry.name) {
+                groups.entry((from, to)).or_default().push((kind, entry));
+            }
+        }
+
+        let mut patches = Vec::new();
+        for ((from, to), entries) in groups {
+            let find = |wanted: &PackageKind| {
+                entries.iter().find(|(kind, _)| match (kind, wanted) {
+                    (PackageKind::Audio(a), PackageKind::Audio(b)) => a.eq_ignore_ascii_case(b),
+                    _ => kind == wanted,
+                })
+            };
+            let wanted = std::iter::once(PackageKind::Game).chain(languages.iter().cloned().map(PackageKind::Audio));
+            let Some(packages) = wanted
+                .map(|kind| {
+                    find(&kind).map(|(_, entry)| PlannedPackage {
+                        kind,
+                        url: entry.url.clone().unwrap_or_default(),
+                        md5: entry.md5.clone(),
+                        size: entry.size,
+                        decompressed_size: 0,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+
+            debug!(%from, %to, "{}", t!(CachedPatchFound));
+            patches.push((from, to, UpdatePlan {
+                from_version: from.to_string(),
+                to_version: to.to_string(),
+                game_biz: game_biz.to_string(),
+                packages,
+            }));
+        }
+
+        Ok(patches)
+    }
+
+    /// 下载完整安装包的计划
+    fn plan_full(&self, game_package: &GamePackage, installed: Option<GameVersion>, languages: &[String], reason: String) -> Result<UpgradePath> {
+        let major = &game_package.main.major;
+        if major.game_pkgs.is_empty() {
+            return Err(Error::Plan(t!(NoFullPackage, major.version)));
+        }
+
+        let mut packages: Vec<PlannedPackage> = major.game_pkgs
+            .iter()
+            .map(|pkg| PlannedPackage {
+                kind: PackageKind::Game,
+                url: pkg.url.clone(),
+                md5: pkg.md5.clone(),
+                size: pkg.size,
+                decompressed_size: pkg.decompressed_size,
+            })
+            .collect();
+        for language in languages {
+            let audio_pkg = major.audio_pkgs
+                .iter()
+                .find(|pkg| pkg.language.eq_ignore_ascii_case(language))
+                .ok_or_else(|| Error::Plan(t!(FullNoLanguage, major.version, language)))?;
+            packages.push(PlannedPackage {
+                kind: PackageKind::Audio(audio_pkg.language.clone()),
+                url: audio_pkg.url.clone(),
+                md5: audio_pkg.md5.clone(),
+                size: audio_pkg.size,
+                decompressed_size: audio_pkg.decompressed_size,
+            });
+        }
+
+        Ok(UpgradePath {
+            route: Route::Full,
+            steps: vec![UpdatePlan {
+                from_version: installed.map(|version| version.to_string()).unwrap_or_default(),
+                to_version: major.version.clone(),
+                game_biz: game_package.game.biz.clone(),
+                packages,
+            }],
+            reason,
+        })
+    }
+
+    /// 根据本地更新包制定计划，清单可用时按文件名匹配以获得 md5 与版本
+    pub fn plan_local(&self, archives: &[PathBuf], response: Option<&Response>) -> Result<UpdatePlan> {
+        let game_package = response.and_then(|response| response.game_package().ok());
+        let mut plan = UpdatePlan {
+            from_version: String::new(),
+            to_version: String::new(),
+            game_biz: game_package.map(|pkg| pkg.game.biz.clone()).unwrap_or_default(),
+            packages: Vec::new(),
+        };
+
+        for archive in archives {
+            let file_name = archive
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .ok_or_else(|| Error::Plan(t!(InvalidPackagePath, archive.display())))?;
+            let matches = |url: &str| url.rsplit('/').next() == Some(file_name.as_str());
+
+            let matched = game_package.and_then(|game_package| {
+                game_package.main.patches.iter().find_map(|patch| {
+                    let package = patch.game_pkgs
+                        .iter()
+                        .find(|pkg| matches(&pkg.url))
+                        .map(|pkg| PlannedPackage {
+                            kind: PackageKind::Game,
+                            url: pkg.url.clone(),
+                            md5: pkg.md5.clone(),
+                            size: pkg.size,
+                            decompressed_size: pkg.decompressed_size,
+                        })
+                        .or_else(|| {
+                            patch.audio_pkgs.iter().find(|pkg| matches(&pkg.url)).map(|pkg| PlannedPackage {
+                                kind: PackageKind::Audio(pkg.language.clone()),
+                                url: pkg.url.clone(),
+                                md5: pkg.md5.clone(),
+                                size: pkg.size,
+                                decompressed_size: pkg.decompressed_size,
+                            })
+                        })?;
+                    Some((patch.version.clone(), game_package.main.major.version.clone(), package))
+                })
+            });
+
+            match matched {
+                Some((from_version, to_version, package)) => {
+                    info!(archive = %archive.display(), from = %from_version, to = %to_version, "{}", t!(PackageInManifest));
+                    plan.from_version = from_version;
+                    plan.to_version = to_version;
+                    plan.packages.push(package);
+                }
+                None => {
+                    warn!(archive = %archive.display(), "{}", t!(PackageNotInManifest));
+                    let language = file_name.split('_').next().filter(|prefix| is_language_code(prefix));
+                    plan.packages.push(PlannedPackage {
+                        kind: language.map_or(PackageKind::Game, |code| PackageKind::Audio(code.to_string())),
+                        url: String::new(),
+                        md5: String::new(),
+                        size: 0,
+                        decompressed_size: 0,
+                    });
+                }
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// 检查本地更新包的大小与 md5
+    pub fn check_archives(&self, plan: &UpdatePlan, archives: &[PathBuf]) -> Result<()> {
+        for (pkg, path) in plan.packages.iter().zip(archives) {
+            let complete = fs::metadata(path).is_ok_and(|meta| meta.len() >= pkg.size);
+            if !complete {
+                return Err(Error::Download {
+                    url: pkg.url.clone(),
+                    message: t!(PackageIncomplete, path.display()),
+                });
+            }
+            verify_md5(path, &pkg.md5)?;
+        }
+        Ok(())
+    }
+
+    /// 下载计划中的所有更新包并校验 md5，返回本地路径
+    /// 离线模式下只检查本地已下载的更新包
+    pub fn download(&self, plan: &UpdatePlan) -> Result<Vec<PathBuf>> {
+        if self.offline {
+            let archives: Vec<PathBuf> = plan.packages.iter().map(|pkg| self.archive_path(pkg)).collect();
+            self.check_archives(plan, &archives)?;
+            return Ok(archives);
+        }
+
+        plan.packages.iter().map(|pkg| self.download_one(pkg, None)).collect()
+    }
+
+    fn download_one(&self, pkg: &PlannedPackage, cancel: Option<&AtomicBool>) -> Result<PathBuf> {
+        let path = self.archive_path(pkg);
+        let existing = file_len(&path).max(file_len(&part_path(&path)));
+        let started = Instant::now();
+        let archive = download_package(&pkg.url, pkg.size, &pkg.md5, &path, &self.settings.download, cancel, self.progress())?;
+
+        let downloaded = file_len(&archive).saturating_sub(existing);
+        if let Err(e) = throughput::record(&self.cache_dir, downloaded, started.elapsed()) {
+            warn!(error = %e, "{}", t!(ThroughputFailed));
+        }
+
+        let cache = self.package_cache();
+        if archive.starts_with(cache.dir()) {
+            cache.record_url(&archive, &pkg.url)?;
+            cache.touch(&archive)?;
+        }
+        Ok(archive)
+    }
+
+    /// 汇总升级路线中的更新包：已缓存的大小、还需下载的大小、预计的磁盘变化与下载时间
+    ///
+    /// 游戏目录的变化按最新完整安装包的解压后大小减去当前 `pkg_version` 中的文件大小估计
+    pub fn summarize(&self, game_package: &GamePackage, path: &UpgradePath) -> Result<PlanSummary> {
+        let mut rows = Vec::new();
+        for plan in &path.steps {
+            for pkg in &plan.packages {
+                rows.push(SummaryRow {
+                    from_version: plan.from_version.clone(),
+                    to_version: plan.to_version.clone(),
+                    kind: pkg.kind.clone(),
+                    size: pkg.size,
+                    cached: file_len(&self.archive_path(pkg)).min(pkg.size),
+                    decompressed_size: pkg.decompressed_size,
+                });
+            }
+        }
+        let download: u64 = rows.iter().map(|row| row.size - row.cached).sum();
+
+        let languages: Vec<&str> = path.steps
+            .last()
+            .into_iter()
+            .flat_map(|plan| &plan.packages)
+            .filter_map(|pkg| match &pkg.kind {
+                PackageKind::Audio(language) => Some(language.as_str()),
+                PackageKind::Game => None,
+            })
+            .collect();
+        let major = &game_package.main.major;
+        let target: Option<u64> = major.game_pkgs
+            .iter()
+            .map(|pkg| pkg.decompressed_size)
+            .chain(languages.iter().map(|language| {
+                major.audio_pkgs
+                    .iter()
+                    .find(|pkg| pkg.language.eq_ignore_ascii_case(language))
+                    .map_or(0, |pkg| pkg.decompressed_size)
+            }))
+            .map(|size| (size > 0).then_some(size))
+            .sum();
+
+        // 只统计游戏本体与所选语言，未更新的语音包大小不变
+        let mut installed = 0;
+        for file in pkg_version_files(&self.game_dir)? {
+            let name = file.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            let selected = name == "pkg_version"
+                || languages.iter().any(|language| {
+                    language_name(language).is_some_and(|dir_name| name.eq_ignore_ascii_case(&format!("Audio_{}_pkg_version", dir_name)))
+                });
+            if selected {
+                installed += read_pkg_version(&file)?.iter().map(|entry| entry.file_size).sum::<u64>();
+            }
+        }
+
+        // 有 md5 的更新包会留在缓存中
+        let cache_growth: u64 = path.steps
+            .iter()
+            .flat_map(|plan| &plan.packages)
+            .zip(&rows)
+            .filter(|(pkg, _)| !pkg.md5.is_empty())
+            .map(|(_, row)| row.size - row.cached)
+            .sum();
+        let disk_change = target.map(|target| target as i64 - installed as i64 + cache_growth as i64);
+
+        Ok(PlanSummary {
+            rows,
+            download,
+            disk_change,
+            eta: throughput::load(&self.cache_dir).map(|throughput| throughput.estimate(download)),
+        })
+    }
+
+    /// 更新包的本地路径：有 md5 时放入缓存，否则放入下载目录
+    fn archive_path(&self, pkg: &PlannedPackage) -> PathBuf {
+        if pkg.md5.is_empty() {
+            package_path(&self.update_dir, &pkg.url)
+        } else {
+            self.package_cache().path_for(&pkg.md5, &pkg.url)
+        }
+    }
+
+    /// 把已下载的更新包全部解压到暂存区后一次性应用，成功后记录新版本、删除下载目录中的更新包并按配置淘汰缓存
+    pub fn apply(&self, plan: &UpdatePlan, archives: &[PathBuf]) -> Result<UpdateStats> {
+        let stats = self.apply_step(plan, archives)?;
+        self.prune_cache();
+        Ok(stats)
+    }
+
+    /// 边下载边解压：后台线程依次下载，解压当前更新包的同时下载后续的更新包，
+    /// 全部解压后一次性应用到游戏目录，成功后按配置淘汰缓存
+    pub fn download_and_apply(&self, plan: &UpdatePlan) -> Result<UpdateStats> {
+        let stats = self.download_and_apply_step(plan)?;
+        self.prune_cache();
+        Ok(stats)
+    }
+
+    /// 依次下载并应用升级路线的每一步，每步的统计追加到 `stats`
+    ///
+    /// 全部步骤完成后才淘汰缓存，后续步骤要用的补丁不会被提前删除
+    pub fn apply_path(&self, path: &UpgradePath, sequential: bool, stats: &mut Vec<UpdateStats>) -> Result<()> {
+        for plan in &path.steps {
+            let step_stats = if sequential {
+                let archives = self.download(plan)?;
+                self.apply_step(plan, &archives)?
+            } else {
+                self.download_and_apply_step(plan)?
+            };
+            stats.push(step_stats);
+        }
+        self.prune_cache();
+        Ok(())
+    }
+
+    fn apply_step(&self, plan: &UpdatePlan, archives: &[PathBuf]) -> Result<UpdateStats> {
+        let (joined, temporary) = join_volumes(archives, &self.update_dir)?;
+        let mut staging = self.staging()?;
+        for archive in &joined {
+            staging.add_package(archive, self.progress())?;
+        }
+        let stats = staging.apply(&self.game_dir, self.progress())?;
+        for path in &temporary {
+            fs::remove_file(path).with_path(path)?;
+        }
+
+        self.finish_apply(plan, archives)?;
+        Ok(stats)
+    }
+
+    fn download_and_apply_step(&self, plan: &UpdatePlan) -> Result<UpdateStats> {
+        // 分卷的完整安装包要全部下载后才能拼接解压
+        if self.offline || plan.packages.iter().any(|pkg| volume_base(&pkg.url).is_some()) {
+            let archives = self.download(plan)?;
+            return self.apply_step(plan, &archives);
+        }
+
+        // 解压失败时通知下载线程立即停止，不必等当前更新包下载完
+        let cancel = AtomicBool::new(false);
+        let (archives, stats) = thread::scope(|scope| {
+            let (tx, rx) = mpsc::channel();
+            let cancel = &cancel;
+            scope.spawn(move || {
+                for pkg in &plan.packages {
+                    let result = self.download_one(pkg, Some(cancel));
+                    let failed = result.is_err();
+                    // 解压失败时接收端已关闭，停止下载
+                    if tx.send(result).is_err() || failed {
+                        break;
+                    }
+                }
+            });
+
+            let result = (|| {
+                let mut staging = self.staging()?;
+                let mut archives = Vec::new();
+                for result in rx {
+                    let archive = result?;
+                    staging.add_package(&archive, self.progress())?;
+                    archives.push(archive);
+                }
+                let stats = staging.apply(&self.game_dir, self.progress())?;
+                Ok::<_, Error>((archives, stats))
+            })();
+            if result.is_err() {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            result
+        })?;
+
+        self.finish_apply(plan, &archives)?;
+        Ok(stats)
+    }
+
+    /// 写入文件的属主：优先使用指定值；以 root 运行时使用游戏目录的属主，游戏目录也属于 root 时不修改
+    fn file_owner(&self) -> Result<Option<Owner>> {
+        if self.owner.is_some() {
+            return Ok(self.owner);
+        }
+        if !is_root() {
+            return Ok(None);
+        }
+        let owner = Owner::of(&self.game_dir).with_path(&self.game_dir)?;
+        Ok((owner.uid != 0).then_some(owner))
+    }
+
+    /// 创建暂存区，默认放在游戏目录下以便直接重命名到位
+    fn staging(&self) -> Result<Staging> {
+        let parent = self.settings.apply.staging_dir.as_ref().unwrap_or(&self.game_dir);
+        fs::create_dir_all(parent).with_path(parent)?;
+        // 暂存目录创建时会被清空，不能包含游戏目录；规范化后比较，相对路径与符号链接绕不过检查
+        let parent = fs::canonicalize(parent).with_path(parent)?;
+        let game_dir = fs::canonicalize(&self.game_dir).with_path(&self.game_dir)?;
+        if game_dir.starts_with(parent.join(STAGING_DIR)) {
+            return Err(Error::Config {
+                path: parent.join(STAGING_DIR),
+                message: t!(StagingContainsGameDir).to_string(),
+            });
+        }
+        let apply = &self.settings.apply;
+        Ok(Staging::new(&parent, self.file_owner()?)?
+            .with_copy_policy(apply.on_copy_error, apply.copy_retries))
+    }
+
+    /// 把版本写入 config.ini，与其他写入的文件一样放宽权限并交给属主
+    fn record_version(&self, version: &str, game_biz: &str) -> Result<()> {
+        let mut modes = ModeGuard::new().with_owner(self.file_owner()?);
+        let config_path = self.game_dir.join(CONFIG_FILE);
+        modes.prepare(&config_path).with_path(&config_path)?;
+        write_game_version(&self.game_dir, version, game_biz)?;
+        modes.restore()
+    }
+
+    /// 记录新版本并删除下载目录中的更新包
+    fn finish_apply(&self, plan: &UpdatePlan, archives: &[PathBuf]) -> Result<()> {
+        if plan.to_version.is_empty() {
+            warn!("{}", t!(UnknownTargetVersion));
+        } else {
+            self.record_version(&plan.to_version, &plan.game_biz)?;
+        }
+
+        // 缓存中的更新包保留给之后的安装或重试，只删除下载目录中的
+        for archive in archives.iter().filter(|archive| archive.starts_with(&self.update_dir)) {
+            fs::remove_file(archive).with_path(archive)?;
+        }
+        Ok(())
+    }
+
+    /// 按配置淘汰缓存，失败时只警告
+    fn prune_cache(&self) {
+        match self.package_cache().prune(&self.settings.cache) {
+            Ok(removed) if !removed.is_empty() => info!(count = removed.len(), "{}", t!(CacheEvicted)),
+            Ok(_) => {}
+            Err(e) => warn!(error = %e, "{}", t!(CachePruneFailed)),
+        }
+    }
+
+    /// 通过 Sophon 分块下载更新到最新版本，只下载本地缺失或已变化的分块
+    pub fn update_sophon(&self, languages: &[String]) -> Result<(String, UpdateStats)> {
+        if self.offline {
+            return Err(Error::Plan(t!(SophonNeedsNetwork).to_string()));
+        }
+
+        let branch = sophon::fetch_branch(sophon::BRANCHES_API_URL)?;
+        let build = sophon::fetch_build(sophon::BUILD_API_URL, &branch)?;
+        info!(tag = %build.tag, "{}", t!(SophonBuild));
+
+        let mut manifests = Vec::new();
+        for field in std::iter::once("game").chain(languages.iter().map(String::as_str)) {
+            let manifest = build.manifests
+                .iter()
+                .find(|manifest| manifest.matching_field.eq_ignore_ascii_case(field))
+                .ok_or_else(|| Error::Plan(t!(SophonNoManifest, build.tag, field)))?;
+            manifests.push(manifest);
+        }
+
+        let chunk_dir = self.update_dir.join(SOPHON_CHUNK_DIR);
+        let owner = self.file_owner()?;
+        let mut stats = UpdateStats::default();
+        for manifest in manifests {
+            let files = sophon::fetch_manifest(manifest)?;
+            let plan = sophon::plan_files(&files, &self.game_dir)?;
+            info!(
+                category = %manifest.matching_field,
+                changed = plan.files.len(),
+                unchanged = plan.unchanged,
+                bytes = plan.download_bytes()?,
+                "{}",
+                t!(SophonPlan)
+            );
+
+            sophon::download_chunks(&plan, &manifest.chunk_download, &chunk_dir, &self.settings.download, self.progress())?;
+            let assembled = sophon::assemble_files(&plan, &manifest.chunk_download, &self.game_dir, &chunk_dir, owner, self.progress())?;
+            stats.packages += 1;
+            stats.copied += assembled.copied;
+            stats.touched.extend(assembled.touched);
+        }
+
+        self.record_version(&build.tag, "")?;
+
+        if chunk_dir.exists() {
+            fs::remove_dir_all(&chunk_dir).with_path(&chunk_dir)?;
+        }
+
+        Ok((build.tag, stats))
+    }
+
+    /// 按 `pkg_version` 检查游戏文件是否存在且大小正确
+    pub fn verify(&self) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+
+        for entry in read_all_pkg_versions(&self.game_dir)? {
+            report.checked += 1;
+            let path = self.game_dir.join(&entry.remote_name);
+            match fs::metadata(&path) {
+                Ok(meta) if meta.len() == entry.file_size => {}
+                Ok(_) => {
+                    warn!(file = %entry.remote_name, "{}", t!(FileSizeMismatch));
+                    report.mismatched.push(entry.remote_name);
+                }
+                Err(_) => {
+                    warn!(file = %entry.remote_name, "{}", t!(FileMissing));
+                    report.missing.push(entry.remote_name);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// 列出不在 `pkg_version` 中的残留文件
+    pub fn find_orphans(&self) -> Result<Vec<Orphan>> {
+        orphans::find_orphans(&self.game_dir, &self.settings.clean.protect)
+    }
+
+    /// 删除残留文件，返回释放的字节数
+    pub fn remove_orphans(&self, orphans: &[Orphan]) -> Result<u64> {
+        orphans::remove_orphans(&self.game_dir, orphans, self.progress())
+    }
+
+    /// 按新的 `pkg_version` 校验本次更新写入或跳过的每个文件的大小与 md5
+    pub fn verify_touched(&self, stats: &[UpdateStats]) -> Result<VerifyReport> {
+        let entries: HashMap<String, PkgEntry> = read_all_pkg_versions(&self.game_dir)?
+            .into_iter()
+            .map(|entry| (entry.remote_name.clone(), entry))
+            .collect();
+
+        // 被跳过的文件同样需要校验，以便交给修复
+        let mut touched: Vec<&String> = stats
+            .iter()
+            .flat_map(|stats| stats.touched.iter().chain(&stats.skipped))
+            .collect();
+        touched.sort();
+        touched.dedup();
+
+        let mut report = VerifyReport::default();
+        self.progress.emit(Event::PhaseStarted { phase: Phase::Verify, total: touched.len() as u64 });
+        for remote_name in touched {
+            let Some(entry) = entries.get(remote_name) else {
+                debug!(file = %remote_name, "{}", t!(SkipUnlisted));
+                self.progress.emit(Event::FileVerified { path: remote_name.clone(), ok: true });
+                continue;
+            };
+
+            report.checked += 1;
+            let path = self.game_dir.join(remote_name);
+            let ok = match fs::metadata(&path) {
+                Ok(meta) if meta.len() == entry.file_size && verify_md5(&path, &entry.md5).is_ok() => true,
+                Ok(_) => {
+                    self.progress.suspend(&mut || warn!(file = %remote_name, "{}", t!(FileMismatch)));
+                    report.mismatched.push(remote_name.clone());
+                    false
+                }
+                Err(_) => {
+                    self.progress.suspend(&mut || warn!(file = %remote_name, "{}", t!(FileMissing)));
+                    report.missing.push(remote_name.clone());
+                    false
+                }
+            };
+            self.progress.emit(Event::FileVerified { path: remote_name.clone(), ok });
+        }
+        self.progress.emit(Event::PhaseFinished { phase: Phase::Verify });
+
+        Ok(report)
+    }
+
+    /// 从 `{res_list_url}/{remoteName}` 重新下载校验失败的文件，返回仍未修复的文件
+    pub fn repair(&self, report: &VerifyReport, res_list_url: &str) -> Result<VerifyReport> {
+        if res_list_url.is_empty() {
+            return Err(Error::Plan(t!(RepairNoResListUrl).to_string()));
+        }
+        if self.offline {
+            return Err(Error::Plan(t!(RepairNeedsNetwork).to_string()));
+        }
+
+        let entries: HashMap<String, PkgEntry> = read_all_pkg_versions(&self.game_dir)?
+            .into_iter()
+            .map(|entry| (entry.remote_name.clone(), entry))
+            .collect();
+
+        let mut remaining = VerifyReport::default();
+        let broken: Vec<(&String, bool)> = report.missing
+            .iter()
+            .map(|name| (name, true))
+            .chain(report.mismatched.iter().map(|name| (name, false)))
+            .collect();
+
+        self.progress.emit(Event::PhaseStarted { phase: Phase::Repair, total: broken.len() as u64 });
+        for (remote_name, missing) in broken {
+            remaining.checked += 1;
+            let result = match entries.get(remote_name) {
+                Some(entry) => self.repair_file(entry, res_list_url),
+                None => Err(Error::Plan(t!(NotInPkgVersion, remote_name))),
+            };
+            match result {
+                Ok(()) => self.progress.suspend(&mut || info!(file = %remote_name, "{}", t!(Repaired))),
+                Err(e) => {
+                    self.progress.suspend(&mut || warn!(file = %remote_name, error = %e, "{}", t!(RepairFailed)));
+                    if missing {
+                        remaining.missing.push(remote_name.clone());
+                    } else {
+                        remaining.mismatched.push(remote_name.clone());
+                    }
+                }
+            }
+            self.progress.emit(Event::FileRepaired { path: remote_name.clone() });
+        }
+        self.progress.emit(Event::PhaseFinished { phase: Phase::Repair });
+
+        Ok(remaining)
+    }
+
+    fn repair_file(&self, entry: &PkgEntry, res_list_url: &str) -> Result<()> {
+        let url = format!("{}/{}", res_list_url.trim_end_matches('/'), entry.remote_name);
+        let downloaded = download_package(&url, entry.file_size, &entry.md5, &package_path(&self.update_dir, &url), &self.settings.download, None, &NoopSink)?;
+
+        let target = self.game_dir.join(&entry.remote_name);
+        let mut modes = ModeGuard::new().with_owner(self.file_owner()?);
+        modes.prepare(&target).with_path(&target)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).with_path(parent)?;
+        }
+        fs::copy(&downloaded, &target).map_err(|source| Error::Copy { path: target.clone(), source })?;
+        fs::remove_file(&downloaded).with_path(&downloaded)?;
+        modes.restore()?;
+
+        Ok(())
+    }
+}
+
+fn file_len(path: &Path) -> u64 {
+    fs::metadata(path).map_or(0, |meta| meta.len())
+}
+
+/// 分卷文件名（`*.zip.001`）去掉序号后的部分
+fn volume_base(name: &str) -> Option<&str> {
+    let (base, index) = name.rsplit_once('.')?;
+    (base.ends_with(".zip") && !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit())).then_some(base)
+}
+
+/// 把分卷的安装包按顺序拼接到下载目录，返回拼接后的更新包列表与需要删除的临时文件
+fn join_volumes(archives: &[PathBuf], update_dir: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut joined: Vec<PathBuf> = Vec::new();
+    let mut temporary = Vec::new();
+    let mut volumes: Vec<(String, Vec<&PathBuf>)> = Vec::new();
+
+    for archive in archives {
+        let name = archive.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        match volume_base(&name) {
+            Some(base) => match volumes.iter_mut().find(|(b, _)| b == base) {
+                Some((_, parts)) => parts.push(archive),
+                None => volumes.push((base.to_string(), vec![archive])),
+            },
+            None => joined.push(archive.clone()),
+        }
+    }
+
+    for (base, mut parts) in volumes {
+        parts.sort_by_key(|part| part.file_name().map(|name| name.to_os_string()));
+        let path = update_dir.join(&base);
+        fs::create_dir_all(update_dir).with_path(update_dir)?;
+        info!(archive = %path.display(), volumes = parts.len(), "{}", t!(JoiningVolumes));
+        let mut out = fs::File::create(&path).with_path(&path)?;
+        for part in parts {
+            let mut input = fs::File::open(part).with_path(part)?;
+            std::io::copy(&mut input, &mut out).with_path(&path)?;
+        }
+        joined.insert(0, path.clone());
+        temporary.push(path);
+    }
+
+    Ok((joined, temporary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::zip_bytes;
+
+    fn sample_response() -> Response {
+        serde_json::from_str(r#"
+        {
+            "retcode": 0,
+            "message": "OK",
+            "data": {
+                "game_packages": [{
+                    "game": {"id": "gopR6Cufr3", "biz": "hk4e_global"},
+                    "main": {
+                        "major": {"version": "5.1.0", "game_pkgs": [], "audio_pkgs": [], "res_list_url": ""},
+                        "patches": [{
+                            "version": "5.0.0",
+                            "game_pkgs": [{"url": "http://example.com/game.zip", "md5": "aa", "size": "10", "decompressed_size": "20"}],
+                            "audio_pkgs": [
+                                {"language": "en-us", "url": "http://example.com/en.zip", "md5": "bb", "size": "1", "decompressed_size": "2"}
+                            ],
+                            "res_list_url": ""
+                        }]
+                    },
+                    "pre_download": {"major": null, "patches": []}
+                }]
+            }
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn test_plan_selects_patch_and_languages() {
+        let updater = Updater::new("game");
+        let plan = updater.plan(&sample_response(), "5.0.0", &["EN-US".to_string()]).unwrap();
+
+        assert_eq!(plan.to_version, "5.1.0");
+        assert_eq!(plan.game_biz, "hk4e_global");
+        assert_eq!(plan.packages.len(), 2);
+        assert_eq!(plan.packages[0].kind, PackageKind::Game);
+        assert_eq!(plan.packages[1].kind, PackageKind::Audio("en-us".to_string()));
+    }
+
+    #[test]
+    fn test_fetch_manifest_uses_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let body = serde_json::to_string(&serde_json::json!({
+            "retcode": 0,
+            "message": "OK",
+            "data": {"game_packages": [{
+                "game": {"id": "gopR6Cufr3", "biz": "hk4e_global"},
+                "main": {"major": {"version": "5.1.0"}, "patches": []}
+            }]}
+        })).unwrap();
+
+        let mut server = mockito::Server::new();
+        let _fresh = server.mock("GET", "/api")
+            .match_header("If-None-Match", mockito::Matcher::Missing)
+            .with_header("ETag", "\"v1\"")
+            .with_body(&body)
+            .create();
+        let _not_modified = server.mock("GET", "/api")
+            .match_header("If-None-Match", "\"v1\"")
+            .with_status(304)
+            .create();
+
+        let updater = Updater::new("game")
+            .with_api_url(format!("{}/api", server.url()))
+            .with_cache_dir(temp_dir.path());
+
+        let first = updater.fetch_manifest().unwrap();
+        assert_eq!(first.game_package().unwrap().main.major.version, "5.1.0");
+
+        let second = updater.fetch_manifest().unwrap();
+        assert_eq!(second.game_package().unwrap().main.major.version, "5.1.0");
+
+        // 离线模式直接读取缓存
+        let offline = Updater::new("game")
+            .with_api_url(format!("{}/api", server.url()))
+            .with_cache_dir(temp_dir.path())
+            .with_offline(true);
+        assert_eq!(offline.fetch_manifest().unwrap().game_package().unwrap().game.id, "gopR6Cufr3");
+    }
+
+    #[test]
+    fn test_plan_local_matches_manifest() {
+        let updater = Updater::new("game");
+        let archives = vec![PathBuf::from("/mnt/usb/game.zip"), PathBuf::from("/mnt/usb/ja-jp_5.0.0_5.1.0.zip")];
+        let plan = updater.plan_local(&archives, Some(&sample_response())).unwrap();
+
+        assert_eq!(plan.from_version, "5.0.0");
+        assert_eq!(plan.to_version, "5.1.0");
+        assert_eq!(plan.packages[0].md5, "aa");
+        assert_eq!(plan.packages[1].kind, PackageKind::Audio("ja-jp".to_string()));
+        assert_eq!(plan.packages[1].md5, "");
+    }
+
+    #[test]
+    fn test_plan_rejects_unknown_version() {
+        let updater = Updater::new("game");
+        assert!(updater.plan(&sample_response(), "4.8.0", &[]).is_err());
+        assert!(updater.plan(&sample_response(), "5.0.0", &["ko-kr".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_plan_upgrade_routes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let updater = Updater::new("game").with_cache_dir(temp_dir.path());
+        let cache = updater.package_cache();
+        for (md5, name) in [("c1", "game_4.8.0_5.0.0_hdiff_a.zip"), ("c2", "en-us_4.8.0_5.0.0_hdiff_b.zip")] {
+            let path = cache.path_for(md5, name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, b"zip").unwrap();
+            cache.record_url(&path, &format!("http://example.com/{}", name)).unwrap();
+        }
+        // 未下载完成的与没有记录下载地址的旧补丁都不能使用
+        let partial = cache.path_for("c3", "game_4.7.0_4.8.0_hdiff_c.zip");
+        fs::create_dir_all(partial.parent().unwrap()).unwrap();
+        fs::write(crate::util::part_path(&partial), b"zi").unwrap();
+        cache.record_url(&partial, "http://example.com/game_4.7.0_4.8.0_hdiff_c.zip").unwrap();
+        let unknown = cache.path_for("c4", "game_4.7.0_4.8.0_hdiff_d.zip");
+        fs::create_dir_all(unknown.parent().unwrap()).unwrap();
+        fs::write(&unknown, b"zip").unwrap();
+        let mut response = sample_response();
+        let game_package = &mut response.data.as_mut().unwrap().game_packages[0];
+        game_package.main.major.game_pkgs = serde_json::from_str(r#"[{"url": "http://example.com/full.zip", "size": "100"}]"#).unwrap();
+        let v = |s: &str| s.parse::<GameVersion>().unwrap();
+        let en = ["en-us".to_string()];
+
+        let direct = updater.plan_upgrade(game_package, Some(v("5.0.0")), &en).unwrap();
+        assert_eq!(direct.route, Route::Direct);
+        assert_eq!(direct.steps.len(), 1);
+        assert_eq!(direct.steps[0].packages[0].md5, "aa");
+
+        let multi_hop = updater.plan_upgrade(game_package, Some(v("4.8.0")), &en).unwrap();
+        assert_eq!(multi_hop.route, Route::MultiHop);
+        let versions: Vec<(&str, &str)> = multi_hop.steps
+            .iter()
+            .map(|plan| (plan.from_version.as_str(), plan.to_version.as_str()))
+            .collect();
+        assert_eq!(versions, vec![("4.8.0", "5.0.0"), ("5.0.0", "5.1.0")]);
+        let cached: Vec<&str> = multi_hop.steps[0].packages.iter().map(|pkg| pkg.md5.as_str()).collect();
+        assert_eq!(cached, vec!["c1", "c2"]);
+        assert_eq!(multi_hop.steps[0].packages[0].url, "http://example.com/game_4.8.0_5.0.0_hdiff_a.zip");
+
+        // 缓存中没有日语的旧补丁，退回完整安装包，而完整安装包中也没有日语
+        assert!(updater.plan_upgrade(game_package, Some(v("4.8.0")), &["ja-jp".to_string()]).is_err());
+        let full = updater.plan_upgrade(game_package, Some(v("4.7.0")), &[]).unwrap();
+        assert_eq!(full.route, Route::Full);
+        assert_eq!(full.steps[0].packages[0].url, "http://example.com/full.zip");
+        assert!(updater.plan_upgrade(game_package, Some(v("5.1.0")), &[]).is_err());
+
+        assert_eq!(volume_base("GenshinImpact_5.1.0.zip.003"), Some("GenshinImpact_5.1.0.zip"));
+        assert_eq!(volume_base("game_5.0.0_5.1.0_hdiff.zip"), None);
+    }
+
+    #[test]
+    fn test_summarize_cached_and_disk_change() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let game_dir = temp_dir.path().join("game");
+        fs::create_dir(&game_dir).unwrap();
+        fs::write(game_dir.join("pkg_version"), "{\"remoteName\": \"a.blk\", \"md5\": \"x\", \"fileSize\": 15}\n").unwrap();
+        fs::write(game_dir.join("Audio_English(US)_pkg_version"), "{\"remoteName\": \"b.pck\", \"md5\": \"y\", \"fileSize\": 1}\n").unwrap();
+        fs::write(game_dir.join("Audio_Japanese_pkg_version"), "{\"remoteName\": \"c.pck\", \"md5\": \"z\", \"fileSize\": 100}\n").unwrap();
+
+        let cache_dir = temp_dir.path().join("cache");
+        let updater = Updater::new(&game_dir).with_cache_dir(&cache_dir);
+        let mut response = sample_response();
+        let game_package = &mut response.data.as_mut().unwrap().game_packages[0];
+        game_package.main.major.game_pkgs = serde_json::from_str(r#"[{"url": "http://example.com/full.zip", "size": "25", "decompressed_size": "30"}]"#).unwrap();
+        game_package.main.major.audio_pkgs = serde_json::from_str(r#"[{"language": "en-us", "url": "http://example.com/full_en.zip", "size": "2", "decompressed_size": "3"}]"#).unwrap();
+        let path = updater.plan_upgrade(game_package, "5.0.0".parse().ok(), &["en-us".to_string()]).unwrap();
+
+        // 游戏补丁已下载 4 字节
+        let archive = updater.archive_path(&path.steps[0].packages[0]);
+        fs::create_dir_all(archive.parent().unwrap()).unwrap();
+        fs::write(&archive, b"part").unwrap();
+
+        let summary = updater.summarize(game_package, &path).unwrap();
+        assert_eq!(summary.rows.len(), 2);
+        assert_eq!(summary.rows[0].cached, 4);
+        assert_eq!(summary.download, 7);
+        // 33 - 16 + 7：未选择的日语不计入
+        assert_eq!(summary.disk_change, Some(24));
+        assert!(summary.eta.is_none());
+
+        throughput::record(&cache_dir, 2 * 1024 * 1024, Duration::from_secs(2)).unwrap();
+        assert!(updater.summarize(game_package, &path).unwrap().eta.is_some());
+    }
+
+    #[test]
+    fn test_verify_touched_and_repair() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let game_dir = temp_dir.path();
+        fs::write(game_dir.join("good.dat"), b"good").unwrap();
+        fs::write(game_dir.join("bad.dat"), b"b4d!").unwrap();
+        fs::write(
+            game_dir.join("pkg_version"),
+            format!(
+                "{{\"remoteName\": \"good.dat\", \"md5\": \"{:x}\", \"fileSize\": 4}}\n\
+                 {{\"remoteName\": \"bad.dat\", \"md5\": \"{:x}\", \"fileSize\": 4}}\n",
+                md5::compute(b"good"),
+                md5::compute(b"bad!"),
+            ),
+        ).unwrap();
+
+        let update_dir = tempfile::TempDir::new().unwrap();
+        let updater = Updater::new(game_dir).with_update_dir(update_dir.path());
+        let stats = UpdateStats {
+            touched: vec!["good.dat".to_string(), "bad.dat".to_string(), "extra.txt".to_string()],
+            ..UpdateStats::default()
+        };
+        let report = updater.verify_touched(&[stats]).unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.mismatched, vec!["bad.dat"]);
+
+        let mut server = mockito::Server::new();
+        let _res = server.mock("GET", "/res/bad.dat").with_body("bad!").expect(1).create();
+        let remaining = updater.repair(&report, &format!("{}/res/", server.url())).unwrap();
+
+        assert!(remaining.is_ok());
+        assert_eq!(fs::read(game_dir.join("bad.dat")).unwrap(), b"bad!");
+        _res.assert();
+    }
+
+    #[test]
+    fn test_download_and_apply_pipeline() {
+
+        let game_dir = tempfile::TempDir::new().unwrap();
+        let cache_dir = tempfile::TempDir::new().unwrap();
+        let game_zip = zip_bytes(&[("GenshinImpact.exe", b"new exe")]);
+        let audio_zip = zip_bytes(&[("Audio/en.pck", b"new audio")]);
+
+        let mut server = mockito::Server::new();
+        let _game = server.mock("GET", "/game.zip").with_body(&game_zip).create();
+        let _audio = server.mock("GET", "/en.zip").with_body(&audio_zip).create();
+
+        let package = |kind, name: &str, data: &[u8]| PlannedPackage {
+            kind,
+            url: format!("{}/{}", server.url(), name),
+            md5: format!("{:x}", md5::compute(data)),
+            size: data.len() as u64,
+            decompressed_size: 0,
+        };
+        let plan = UpdatePlan {
+            from_version: "5.0.0".to_string(),
+            to_version: "5.1.0".to_string(),
+            game_biz: "hk4e_global".to_string(),
+            packages: vec![
+                package(PackageKind::Game, "game.zip", &game_zip),
+                package(PackageKind::Audio("en-us".to_string()), "en.zip", &audio_zip),
+            ],
+        };
+
+        let updater = Updater::new(game_dir.path()).with_cache_dir(cache_dir.path());
+        let stats = updater.download_and_apply(&plan).unwrap();
+
+        assert_eq!(stats.packages, 2);
+        assert_eq!(fs::read(game_dir.path().join("GenshinImpact.exe")).unwrap(), b"new exe");
+        assert_eq!(fs::read(game_dir.path().join("Audio/en.pck")).unwrap(), b"new audio");
+        assert_eq!(read_game_version(game_dir.path()).unwrap().as_deref(), Some("5.1.0"));
+        assert_eq!(updater.package_cache().list().unwrap().len(), 2);
+        assert!(!game_dir.path().join(STAGING_DIR).exists());
+    }
+
+    #[test]
+    fn test_apply_path_prunes_after_last_step() {
+        use std::time::SystemTime;
+
+        let game_dir = tempfile::TempDir::new().unwrap();
+        let cache_dir = tempfile::TempDir::new().unwrap();
+        let first_zip = zip_bytes(&[("a.dat", b"5.1")]);
+        let second_zip = zip_bytes(&[("a.dat", b"5.2")]);
+
+        let mut server = mockito::Server::new();
+        let _first = server.mock("GET", "/first.zip").with_body(&first_zip).create();
+
+        let step = |from: &str, to: &str, url: String, data: &[u8]| UpdatePlan {
+            from_version: from.to_string(),
+            to_version: to.to_string(),
+            game_biz: "hk4e_global".to_string(),
+            packages: vec![PlannedPackage {
+                kind: PackageKind::Game,
+                url,
+                md5: format!("{:x}", md5::compute(data)),
+                size: data.len() as u64,
+                decompressed_size: 0,
+            }],
+        };
+        let path = UpgradePath {
+            route: Route::MultiHop,
+            steps: vec![
+                step("5.0.0", "5.1.0", format!("{}/first.zip", server.url()), &first_zip),
+                // 第二步的补丁只在缓存中，且很久没有用过
+                step("5.1.0", "5.2.0", "game_5.1.0_5.2.0_hdiff.zip".to_string(), &second_zip),
+            ],
+            reason: String::new(),
+        };
+
+        let updater = Updater::new(game_dir.path()).with_cache_dir(cache_dir.path());
+        let cached = updater.archive_path(&path.steps[1].packages[0]);
+        fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        fs::write(&cached, &second_zip).unwrap();
+        let long_ago = SystemTime::now() - Duration::from_secs(90 * 24 * 60 * 60);
+        fs::File::options().write(true).open(&cached).unwrap().set_modified(long_ago).unwrap();
+
+        let mut stats = Vec::new();
+        updater.apply_path(&path, false, &mut stats).unwrap();
+
+        assert_eq!(stats.len(), 2);
+        assert_eq!(fs::read(game_dir.path().join("a.dat")).unwrap(), b"5.2");
+        assert_eq!(read_game_version(game_dir.path()).unwrap().as_deref(), Some("5.2.0"));
+        assert_eq!(updater.package_cache().list().unwrap().len(), 2);
+    }
+}
@@ -1,20 +1,32 @@
 
-use std::{fs::OpenOptions, io::{Seek, SeekFrom, Write, copy}, thread, time::Duration, process::Command, io};
+use std::{fs::OpenOptions, io::Write, thread, time::Duration};
+use std::sync::atomic::{AtomicBool, Ordering};
 use reqwest::blocking::Client;
 use reqwest::header::{RANGE, USER_AGENT};
-use anyhow::{Result, anyhow};
+use crate::error::{Error, IoContext, Result};
 
 use serde::Deserialize;
 use std::fs::{self, File};
 use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
-use fs_extra::file::{copy_with_progress, CopyOptions, TransitProcess};
-use crate::{UNPACK_DIR, UPDATE_DIR};
 
-use indicatif::{ProgressBar, ProgressStyle};
+use tracing::{debug, info, warn};
+use crate::progress::{Event, Phase, ProgressSink};
+use crate::settings::DownloadSettings;
+use crate::t;
 
-/// 下载文件，支持断点续传与失败重试
-pub fn download_with_resume(url: &str, output_path: &str, max_retries: u8) -> Result<()> {
+fn is_cancelled(cancel: Option<&AtomicBool>) -> bool {
+    cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+}
+
+/// 下载文件，支持断点续传与失败重试；`cancel` 被置位时停止下载，已下载的部分保留用于续传
+pub fn download_with_resume(
+    url: &str,
+    output_path: &str,
+    max_retries: u8,
+    cancel: Option<&AtomicBool>,
+    progress: &dyn ProgressSink,
+) -> Result<()> {
     let client = Client::new();
 
     let mut retries = 0;
@@ -22,53 +34,80 @@ pub fn download_with_resume(url: &str, output_path: &str, max_retries: u8) -> Re
 
     // 如果已有部分文件，获取已下载大小
     if Path::new(output_path).exists() {
-        downloaded = fs::metadata(output_path)?.len();
+        downloaded = fs::metadata(output_path).with_path(output_path)?.len();
     }
 
     loop {
+        if is_cancelled(cancel) {
+            return Err(Error::Cancelled(t!(DownloadCancelled).to_string()));
+        }
+        debug!(url, offset = downloaded, "{}", t!(RequestSent));
         let resp = client
             .get(url)
             .header(USER_AGENT, "genshin-updater")
             .header(RANGE, format!("bytes={}-", downloaded))
             .send();
 
+        // 服务器返回错误状态时同样视为失败并重试
+        let resp = resp.and_then(|res| res.error_for_status());
+
         match resp {
             Ok(mut res) => {
-                let total_size = res
+                debug!(url, status = %res.status(), "{}", t!(ServerResponded));
+
+                // 服务器不支持断点续传时会返回完整内容，需要从头写入
+                if downloaded > 0 && res.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+                    warn!(url, "{}", t!(ResumeUnsupported));
+                    File::create(output_path).with_path(output_path)?;
+                    downloaded = 0;
+                }
+                let total_size = downloaded + res
                     .headers()
                     .get("Content-Length")
                     .and_then(|len| len.to_str().ok()?.parse::<u64>().ok())
                     .unwrap_or(0);
 
-                let pb = ProgressBar::new(total_size);
-                pb.set_style(ProgressStyle::with_template(
-                    "[{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})",
-                )?
-                .progress_chars("=>-"));
+                progress.emit(Event::PhaseStarted { phase: Phase::Download, total: total_size });
 
                 let mut file = OpenOptions::new()
                     .create(true)
                     .append(true)
-                    .open(output_path)?;
+                    .open(output_path)
+                    .with_path(output_path)?;
 
                 let mut buffer = [0; 8192];
                 loop {
-                    let read = res.read(&mut buffer)?;
+                    if is_cancelled(cancel) {
+                        return Err(Error::Cancelled(t!(DownloadCancelled).to_string()));
+                    }
+                    let read = res.read(&mut buffer).map_err(|e| Error::Download {
+                        url: url.to_string(),
+                        message: e.to_string(),
+                    })?;
                     if read == 0 {
                         break;
                     }
-                    file.write_all(&buffer[..read])?;
-                    pb.inc(read as u64);
+                    file.write_all(&buffer[..read]).with_path(output_path)?;
+                    downloaded += read as u64;
+                    progress.emit(Event::BytesDownloaded {
+                        url: url.to_string(),
+                        downloaded,
+                        total: total_size,
+                    });
                 }
 
-                pb.finish_with_message("✅ 下载完成");
+                progress.emit(Event::PhaseFinished { phase: Phase::Download });
+                info!(url, path = output_path, "{}", t!(DownloadDone));
                 return Ok(());
             }
             Err(e) => {
                 retries += 1;
-                eprintln!("⚠️ 下载失败（第 {} 次尝试）：{}", retries, e);
+                warn!(url, retries, error = %e, "{}", t!(DownloadRetry, retries));
                 if retries >= max_retries {
-                    return Err(anyhow!("❌ 超过最大重试次数，下载失败"));
+                    return Err(Error::Download {
+                        url: url.to_string(),
+                        message: t!(RetriesExceeded, e),
+                    });
                 }
                 thread::sleep(Duration::from_secs(3));
             }
@@ -88,11 +127,11 @@ pub fn parse_line_json(
 ) -> Result<Vec<String>> {
     let mut files = Vec::new();
 
-    let file = File::open(json_lines_path)?;
+    let file = File::open(json_lines_path).with_path(json_lines_path)?;
     let reader = BufReader::new(file);
 
     for (idx, line_result) in reader.lines().enumerate() {
-        let line = line_result?;
+        let line = line_result.with_path(json_lines_path)?;
         if line.trim().is_empty() {
             continue;
         }
@@ -100,7 +139,7 @@ pub fn parse_line_json(
         let entry: FileEntry = match serde_json::from_str(&line) {
             Ok(val) => val,
             Err(e) => {
-                eprintln!("⚠️ 第 {} 行解析失败: {}", idx + 1, e);
+                warn!(file = %json_lines_path.display(), line = idx + 1, error = %e, "{}", t!(LineParseFailed, idx + 1));
                 continue;
             }
         };
@@ -130,211 +169,135 @@ pub fn parse_line_json(
     Ok(files)
 }
 
-// 新增函数：处理单个更新包
-pub fn process_update_package(url: String, siz: u64, game_dir: &Path) -> Result<()> {
-    fs::create_dir_all(UPDATE_DIR)?;
-    fs::create_dir_all(UNPACK_DIR)?;
-
-    let file_name = format!("{}/{}", UPDATE_DIR, url.split('/').last().unwrap());
-
-    println!("📥 下载链接: {}", url);
-    if !Path::new(&file_name).exists() || fs::metadata(&file_name)?.len() < siz {
-        println!("⬇️ 正在下载...");
-        download_with_resume(&url, &file_name, 5)?;
-    }
+/// 一次应用的处理统计
+#[derive(Debug, Default, Clone)]
+pub struct UpdateStats {
+    /// 一起应用的更新包数量
+    pub packages: u64,
+    pub patched: u64,
+    pub deleted: u64,
+    pub copied: u64,
+    /// 写入游戏目录的文件（`pkg_version` 中的 `remoteName` 格式）
+    pub touched: Vec<String>,
+    /// 放置失败被跳过的文件，校验时一并检查
+    pub skipped: Vec<String>,
+}
 
-    use indicatif::{ProgressBar, ProgressStyle};
-
-    println!("📦 正在解压...");
-    let zipfile = File::open(&file_name)?;
-    let mut archive = zip::ZipArchive::new(zipfile)?;
-
-    ensure_writable(Path::new(UNPACK_DIR))?;
-
-    let file_count = archive.len();
-    let pb = ProgressBar::new(file_count as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("📦 解压中 {wide_bar} {pos}/{len} {msg}")
-            .unwrap()
-    );
-
-    for i in 0..file_count {
-        let mut file = archive.by_index(i)?;
-        let outpath = Path::new(UNPACK_DIR).join(file.sanitized_name());
-
-        // 创建文件夹结构
-        if file.is_dir() {
-            fs::create_dir_all(&outpath)?;
-        } else {
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(p)?;
-                }
-            }
-            let mut outfile = File::create(&outpath)?;
-            io::copy(&mut file, &mut outfile)?;
+/// 计算文件的 md5（小写十六进制）
+pub fn file_md5(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_path(path)?;
+    let mut context = md5::Context::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).with_path(path)?;
+        if read == 0 {
+            break;
         }
-
-        pb.inc(1);
+        context.consume(&buffer[..read]);
     }
-    pb.finish_with_message("📦 解压完成");
-
-
-    let update_dir = Path::new(UNPACK_DIR);
-
-    // 获取游戏安装目录路径
-    let genshin_root = Path::new(game_dir);
-
-    // 1. 处理hdifffiles.txt
-    let hdiff_files_path = update_dir.join("hdifffiles.txt");
-    if hdiff_files_path.exists() {
-        let files_to_patch = parse_line_json(&hdiff_files_path)?;
-        let pb = ProgressBar::new(files_to_patch.len() as u64);
-        pb.set_style(ProgressStyle::default_bar()
-            .template("{prefix:.green} {wide_bar} {pos}/{len} {msg}")
-            .unwrap());
-
-        pb.set_prefix("🔧 补丁中");
-
-        for remote_name in files_to_patch {
-            let hdiff_path = update_dir.join(format!("{}.hdiff", remote_name));
-            let target_path = genshin_root.join(&remote_name);
-            let dest_path = update_dir.join(&remote_name);
+    Ok(format!("{:x}", context.compute()))
+}
 
-            if !target_path.exists() {
-                pb.println(format!("⚠️ 跳过不存在文件: {}", target_path.display()));
-                pb.inc(1);
-                continue;
-            }
+/// 校验文件 md5，`expected` 为空时跳过
+pub fn verify_md5(path: &Path, expected: &str) -> Result<()> {
+    if expected.is_empty() {
+        return Ok(());
+    }
+    let actual = file_md5(path)?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(Error::Checksum {
+            path: path.to_path_buf(),
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+    debug!(path = %path.display(), md5 = expected, "{}", t!(Md5Verified));
+    Ok(())
+}
 
-            let status = Command::new("./hpatchz")
-                .arg(&target_path)
-                .arg(&hdiff_path)
-                .arg(&dest_path)
-                .status()?;
+/// 未下载完成的文件名后缀，校验通过后才重命名为最终文件名
+pub const PART_SUFFIX: &str = ".part";
 
-            if !status.success() {
-                return Err(anyhow!("❌ 补丁失败: {}", remote_name));
-            }
+/// `path` 下载过程中使用的临时路径
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_os_string();
+    part.push(PART_SUFFIX);
+    PathBuf::from(part)
+}
 
-            fs::remove_file(&hdiff_path)?;
-            pb.inc(1);
-        }
+/// 更新包在下载目录 `update_dir` 中的本地路径
+pub fn package_path(update_dir: &Path, url: &str) -> PathBuf {
+    update_dir.join(url.split('/').next_back().unwrap())
+}
 
-        pb.finish_with_message("🔧 补丁完成");
-        fs::remove_file(&hdiff_files_path)?;
+/// 下载更新包到 `dest`，已完整下载时跳过，`md5` 非空时校验
+///
+/// 下载中的数据写入 `.part` 文件，校验通过后才重命名为 `dest`
+pub fn download_package(
+    url: &str,
+    siz: u64,
+    md5: &str,
+    dest: &Path,
+    settings: &DownloadSettings,
+    cancel: Option<&AtomicBool>,
+    progress: &dyn ProgressSink,
+) -> Result<PathBuf> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).with_path(parent)?;
     }
 
-    // 2. 处理deletefiles.txt
-    let delete_files_path = update_dir.join("deletefiles.txt");
-    if delete_files_path.exists() {
-        let data = fs::read_to_string(delete_files_path)?;
+    if fs::metadata(dest).is_ok_and(|meta| meta.len() >= siz) && verify_md5(dest, md5).is_ok() {
+        debug!(path = %dest.display(), "{}", t!(PackageExists));
+        return Ok(dest.to_path_buf());
+    }
+    // 最终路径上只会有校验过的完整文件，不完整或损坏的直接删除
+    if dest.exists() {
+        fs::remove_file(dest).with_path(dest)?;
+    }
 
-        for line in data.lines() {
-            let path = line.trim();
-            if path.is_empty() {
+    let part = part_path(dest);
+    let file_name = part.to_string_lossy().to_string();
+    let is_complete = || fs::metadata(&part).is_ok_and(|meta| meta.len() >= siz);
+
+    let mut last_err = None;
+    for candidate in settings.candidate_urls(url) {
+        info!(url = %candidate, "{}", t!(DownloadUrl));
+        if !is_complete() {
+            info!(path = %dest.display(), size = siz, "{}", t!(Downloading));
+            if let Err(e) = download_with_resume(&candidate, &file_name, settings.retries.max(1), cancel, progress) {
+                if matches!(e, Error::Cancelled(_)) {
+                    return Err(e);
+                }
+                warn!(url = %candidate, error = %e, "{}", t!(DownloadFailedNext));
+                last_err = Some(e);
                 continue;
             }
-
-            let delete_path = genshin_root.join(path);
-            if delete_path.exists() {
-                println!("🗑️ 正在删除: {}", path);
-                fs::remove_file(&delete_path)
-                    .or_else(|_| fs::remove_dir_all(&delete_path))?;
-            }
         }
-        fs::remove_file(&update_dir.join("deletefiles.txt"))?;
-    }
 
-    println!("📁 正在复制更新文件...");
-    let skip_files = [
-        "hdifffiles.txt",
-        "deletefiles.txt",
-        ".hdiff"  // 跳过补丁文件
-    ];
-
-    // 使用 walkdir 遍历目录
-    let all_files: Vec<_> = walkdir::WalkDir::new(&update_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .filter(|e| {
-            let p = e.path().to_string_lossy();
-            !skip_files.iter().any(|ext| p.ends_with(ext))
-        })
-        .collect();
-
-    let pb = ProgressBar::new(all_files.len() as u64);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("📄 复制中 {wide_bar} {pos}/{len} {msg}")
-        .unwrap());
-
-    for entry in all_files {
-        let source_path = entry.path();
-        let relative_path = source_path.strip_prefix(&update_dir)?;
-        let dest_path = game_dir.join(relative_path);
-
-        if let Some(parent) = dest_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-
-        fs::copy(source_path, &dest_path).or_else(|err| {
-            println!("Error: {}", err);
-            println!("Continue(y/n)?");
-            let mut choice = String::new();
-            io::stdin()
-                .read_line(&mut choice)
-                .expect("input error.");
-            let choice = choice.to_lowercase();
-            if choice == "y" {
-                Ok(0)
-            } else {
-                Err(anyhow!("Error and canceled by user."))
+        match verify_md5(&part, md5) {
+            Ok(()) => {
+                fs::rename(&part, dest).with_path(dest)?;
+                return Ok(dest.to_path_buf());
+            }
+            Err(e) => {
+                // 损坏的文件不能用于续传，删除后从下一个地址重新下载
+                warn!(url = %candidate, error = %e, "{}", t!(ChecksumFailedNext));
+                fs::remove_file(&part).with_path(&part)?;
+                last_err = Some(e);
             }
-        })?;
-        pb.inc(1);
-    }
-    pb.finish_with_message("📄 文件复制完成");
-
-
-    println!("🧹 清理临时文件...");
-    fs::remove_dir_all(UNPACK_DIR)?;
-    fs::remove_dir_all(UPDATE_DIR)?;
-
-    Ok(())
-}
-
-use std::fs::Permissions;
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
-
-/// 将目标路径的权限设置为可读写（644）
-/// 可递归设置目录下所有子文件
-#[cfg(unix)]
-pub fn ensure_writable(path: &Path) -> std::io::Result<()> {
-    if path.is_file() {
-        fs::set_permissions(path, Permissions::from_mode(0o644))?;
-    } else if path.is_dir() {
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            ensure_writable(&entry.path())?;
         }
-        fs::set_permissions(path, Permissions::from_mode(0o755))?; // 目录 rwx
     }
-    Ok(())
-}
 
-#[cfg(not(unix))]
-fn ensure_writable(_path: &Path) -> std::io::Result<()> {
-    Ok(()) // noop on Windows for now
+    Err(last_err.unwrap_or_else(|| Error::Download {
+        url: url.to_string(),
+        message: t!(NoDownloadUrl).to_string(),
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
-    use mockito::mock;
 
     #[test]
     fn test_parse_line_json() {
@@ -358,10 +321,11 @@ mod tests {
         use std::fs::File;
         use std::io::Write;
         use tempfile::TempDir;
-        use mockito::{mock, Matcher};
+        use mockito::Matcher;
 
         // 模拟服务器支持 Range 请求并返回剩余内容
-        let _m1 = mock("GET", "/test.txt")
+        let mut server = mockito::Server::new();
+        let _m1 = server.mock("GET", "/test.txt")
             .match_header("Range", Matcher::Exact("bytes=5-".to_string()))
             .with_status(206)
             .with_header("Content-Length", "6")
@@ -379,12 +343,33 @@ mod tests {
 
         // 执行断点续传下载
         download_with_resume(
-            &format!("{}/test.txt", mockito::server_url()),
+            &format!("{}/test.txt", server.url()),
             test_file.to_str().unwrap(),
-            3
+            3,
+            None,
+            &crate::progress::NoopSink
         ).unwrap();
 
         // 最终内容应该是完整的 "hello world"
         assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "hello world");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_download_stops_when_cancelled() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        let mut server = mockito::Server::new();
+        let m = server.mock("GET", "/test.txt").with_body("hello world").expect(0).create();
+
+        let cancel = AtomicBool::new(true);
+        let result = download_with_resume(
+            &format!("{}/test.txt", server.url()),
+            test_file.to_str().unwrap(),
+            3,
+            Some(&cancel),
+            &crate::progress::NoopSink
+        );
+        assert!(matches!(result, Err(Error::Cancelled(_))));
+        m.assert();
+    }
+}
@@ -0,0 +1,162 @@
+//! 游戏版本号与升级路线
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
+use crate::language::is_language_code;
+use crate::updater::{PackageKind, UpdatePlan};
+use crate::t;
+
+/// 游戏版本号，如 `5.1.0`；省略的部分视为 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl GameVersion {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        GameVersion { major, minor, patch }
+    }
+}
+
+impl FromStr for GameVersion {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::Plan(t!(InvalidVersion, value));
+        let parts = value
+            .trim()
+            .split('.')
+            .map(|part| part.parse::<u32>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        match parts[..] {
+            [major] => Ok(GameVersion::new(major, 0, 0)),
+            [major, minor] => Ok(GameVersion::new(major, minor, 0)),
+            [major, minor, patch] => Ok(GameVersion::new(major, minor, patch)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// 从补丁文件名 `<game|语言>_<起始版本>_<目标版本>_hdiff_*.zip` 中取出类型与版本
+pub fn parse_patch_name(name: &str) -> Option<(PackageKind, GameVersion, GameVersion)> {
+    let mut parts = name.split('_');
+    let kind = match parts.next()? {
+        "game" => PackageKind::Game,
+        language if is_language_code(language) => PackageKind::Audio(language.to_string()),
+        _ => return None,
+    };
+    let from = parts.next()?.parse().ok()?;
+    let to = parts.next()?.parse().ok()?;
+    parts.next().filter(|part| part.starts_with("hdiff"))?;
+    Some((kind, from, to))
+}
+
+/// 用最少的补丁从 `installed` 升级到 `latest`，返回依次使用的 `edges` 下标
+///
+/// `edges` 为各补丁的起始与目标版本；跳数相同时优先跨度大的补丁
+pub fn find_hops(installed: GameVersion, latest: GameVersion, edges: &[(GameVersion, GameVersion)]) -> Option<Vec<usize>> {
+    let mut order: Vec<usize> = (0..edges.len()).filter(|&idx| edges[idx].0 < edges[idx].1).collect();
+    order.sort_by_key(|&idx| std::cmp::Reverse(edges[idx].1));
+
+    // 版本 → 到达该版本使用的补丁
+    let mut reached: HashMap<GameVersion, Option<usize>> = HashMap::from([(installed, None)]);
+    let mut queue = VecDeque::from([installed]);
+    while let Some(version) = queue.pop_front() {
+        if version == latest {
+            break;
+        }
+        for &idx in order.iter().filter(|&&idx| edges[idx].0 == version) {
+            let to = edges[idx].1;
+            if to <= latest && !reached.contains_key(&to) {
+                reached.insert(to, Some(idx));
+                queue.push_back(to);
+            }
+        }
+    }
+
+    let mut hops = Vec::new();
+    let mut version = latest;
+    while let Some(idx) = *reached.get(&version)? {
+        hops.push(idx);
+        version = edges[idx].0;
+    }
+    hops.reverse();
+    Some(hops)
+}
+
+/// 升级方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// 一个补丁即可从已安装版本升级到最新版本
+    Direct,
+    /// 依次应用缓存中的旧补丁与清单中的补丁
+    MultiHop,
+    /// 下载完整安装包
+    Full,
+}
+
+/// 从已安装版本到最新版本的升级路线，每一步单独应用并记录版本
+#[derive(Debug, Clone)]
+pub struct UpgradePath {
+    pub route: Route,
+    pub steps: Vec<UpdatePlan>,
+    /// 选择该路线的原因
+    pub reason: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_order_versions() {
+        let v = |s: &str| s.parse::<GameVersion>().unwrap();
+        assert_eq!(v("5.1.0"), GameVersion::new(5, 1, 0));
+        assert_eq!(v("5.1"), v("5.1.0"));
+        assert!(v("4.8.0") < v("5.0.0"));
+        assert!(v("5.10.0") > v("5.9.1"));
+        assert_eq!(v(" 5.2.1 ").to_string(), "5.2.1");
+        assert!("5.1.x".parse::<GameVersion>().is_err());
+        assert!("5.1.0.1".parse::<GameVersion>().is_err());
+
+        assert_eq!(
+            parse_patch_name("game_4.8.0_5.0.0_hdiff_AbCdEf.zip"),
+            Some((PackageKind::Game, v("4.8.0"), v("5.0.0")))
+        );
+        assert_eq!(
+            parse_patch_name("en-us_4.8.0_5.0.0_hdiff_AbCdEf.zip"),
+            Some((PackageKind::Audio("en-us".to_string()), v("4.8.0"), v("5.0.0")))
+        );
+        assert_eq!(parse_patch_name("GenshinImpact_5.1.0.zip.001"), None);
+    }
+
+    #[test]
+    fn test_find_hops() {
+        let v = |s: &str| s.parse::<GameVersion>().unwrap();
+        let edges = [
+            (v("4.8.0"), v("5.0.0")),
+            (v("5.0.0"), v("5.1.0")),
+            (v("4.7.0"), v("4.8.0")),
+            (v("4.8.0"), v("4.8.1")),
+            (v("4.8.1"), v("5.1.0")),
+            (v("4.6.0"), v("4.7.0")),
+        ];
+
+        assert_eq!(find_hops(v("5.0.0"), v("5.1.0"), &edges), Some(vec![1]));
+        // 两条两跳的路线中选第一跳跨度大的
+        assert_eq!(find_hops(v("4.8.0"), v("5.1.0"), &edges), Some(vec![0, 1]));
+        assert_eq!(find_hops(v("4.6.0"), v("5.1.0"), &edges), Some(vec![5, 2, 0, 1]));
+        assert_eq!(find_hops(v("4.5.0"), v("5.1.0"), &edges), None);
+    }
+}